@@ -0,0 +1,69 @@
+use std::hint::black_box;
+use std::slice;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use squadro_solver::board_state::BoardState;
+use squadro_solver::file_operations::{self, DataFormat};
+use squadro_solver::generate::collect_reachable_states;
+
+// Board states from the beginning, the middle and the end of a game.
+const STATE_IDS: [u64; 3] = [1, 85065666045, 100382226046];
+
+// Initial board state of the small subtree used by the other benchmarks.
+const SUBTREE_INIT_ID: u64 = 85065666045;
+
+fn next_state(c: &mut Criterion) {
+    let states = STATE_IDS.map(BoardState::from);
+
+    c.bench_function("get_next_state", |b| {
+        b.iter(|| {
+            for state in &states {
+                for piece in 0..5 {
+                    black_box(black_box(state).get_next_state(piece));
+                }
+            }
+        })
+    });
+}
+
+fn reachable_states(c: &mut Criterion) {
+    let init_state = BoardState::from(SUBTREE_INIT_ID);
+
+    c.bench_function("collect_reachable_states", |b| {
+        b.iter(|| collect_reachable_states(slice::from_ref(black_box(&init_state))))
+    });
+}
+
+fn state_value(c: &mut Criterion) {
+    let tmp = tempfile::TempDir::new().unwrap();
+    let states = collect_reachable_states(&[BoardState::from(SUBTREE_INIT_ID)]);
+
+    // Query both present and absent states.
+    let ids: Vec<u64> = states.iter().flat_map(|id| [id, id + 1]).take(16).collect();
+
+    for format in [DataFormat::Zip, DataFormat::Gzip, DataFormat::Roaring] {
+        let path_buf = tmp.path().join(format!("{:?}.data", format));
+        let path = path_buf.to_str().unwrap();
+        file_operations::write_states(
+            file_operations::create_new_file(path),
+            path,
+            &states,
+            format,
+        )
+        .unwrap();
+
+        c.bench_function(&format!("read_state_value ({:?})", format), |b| {
+            b.iter(|| {
+                for &id in &ids {
+                    black_box(file_operations::read_state_value(path, black_box(id)).unwrap());
+                }
+            })
+        });
+    }
+
+    tmp.close().unwrap();
+}
+
+criterion_group!(benches, next_state, reachable_states, state_value);
+criterion_main!(benches);
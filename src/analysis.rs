@@ -0,0 +1,910 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error;
+use std::fmt::{self, Write};
+use std::io::{self, BufRead};
+
+use roaring::RoaringTreemap;
+
+use crate::board_state::{BoardState, Move, Player, Ruleset};
+use crate::database::{BoardStateEval, StateDatabase};
+use crate::file_operations::{self, DataError, DataFormat};
+
+/// Return the states stored only in data file `path_a` and those stored only in data file `path_b`
+pub fn diff(path_a: &str, path_b: &str) -> Result<(RoaringTreemap, RoaringTreemap), DataError> {
+    let states_a = file_operations::read_states(path_a)?;
+    let states_b = file_operations::read_states(path_b)?;
+
+    Ok((&states_a - &states_b, &states_b - &states_a))
+}
+
+/// Print the differences between data files `path_a` and `path_b`, with at most `sample_size` IDs for each file
+pub fn print_diff(path_a: &str, path_b: &str, sample_size: usize) {
+    let (only_a, only_b) = diff(path_a, path_b).unwrap_or_else(|e| panic!("{}", e));
+
+    for (path, states) in [(path_a, &only_a), (path_b, &only_b)] {
+        println!("{} states only in {}.", states.len(), path);
+
+        let sample: Vec<String> = states
+            .iter()
+            .take(sample_size)
+            .map(|id| id.to_string())
+            .collect();
+        if !sample.is_empty() {
+            println!("  {}", sample.join(", "));
+        }
+    }
+}
+
+/// Write to the new data file `output_path`, in the given `format`, all states stored in at least one of the data files `input_paths`
+///
+/// Return the number of written states. Terminate thread if `output_path` already exists, which is checked after
+/// reading all input files.
+pub fn merge(
+    input_paths: &[String],
+    output_path: &str,
+    format: DataFormat,
+) -> Result<u64, DataError> {
+    let mut states = RoaringTreemap::new();
+    for path in input_paths {
+        states |= file_operations::read_states(path)?;
+    }
+
+    let output_file = file_operations::create_new_file(output_path);
+    file_operations::write_states(output_file, output_path, &states, format)?;
+
+    Ok(states.len())
+}
+
+/// Return an iterator over the states stored in data file `path`, in increasing order
+///
+/// Only one chunk of the file is decompressed at a time.
+pub fn list_ids(path: &str) -> Result<impl Iterator<Item = Result<u64, DataError>>, DataError> {
+    Ok(
+        file_operations::read_chunks(path)?.flat_map(|chunk| match chunk {
+            Ok((chunk_id, chunk_buffer)) => {
+                file_operations::chunk_state_ids(&chunk_buffer, chunk_id)
+                    .map(Ok)
+                    .collect::<Vec<_>>()
+            }
+            Err(e) => vec![Err(e)],
+        }),
+    )
+}
+
+/// Print the states stored in data file `path`, one per line, stopping after `limit` states if provided
+pub fn print_ids(path: &str, limit: Option<usize>) {
+    let ids = list_ids(path).unwrap_or_else(|e| panic!("{}", e));
+
+    for id in ids.take(limit.unwrap_or(usize::MAX)) {
+        println!("{}", id.unwrap_or_else(|e| panic!("{}", e)));
+    }
+}
+
+/// Return the number of states stored in data file `path` for each number of legal moves, from 0 to 5
+///
+/// States are read following `ruleset`, one chunk at a time. Ended states have no legal moves.
+pub fn branching_histogram(path: &str, ruleset: &'static Ruleset) -> Result<[u64; 6], DataError> {
+    let mut histogram = [0; 6];
+
+    for id in list_ids(path)? {
+        let state = BoardState::from_id_with_ruleset(id?, ruleset);
+        histogram[state.legal_moves_mask().count_ones() as usize] += 1;
+    }
+
+    Ok(histogram)
+}
+
+/// Print the number and proportion of states stored in data file `path` for each number of legal moves
+pub fn print_branching_histogram(path: &str, ruleset: &'static Ruleset) {
+    let histogram = branching_histogram(path, ruleset).unwrap_or_else(|e| panic!("{}", e));
+    let total: u64 = histogram.iter().sum();
+
+    for (moves, count) in histogram.iter().enumerate() {
+        println!(
+            "{} legal move(s) : {} states ({:.2}%)",
+            moves,
+            count,
+            *count as f64 * 100.0 / total.max(1) as f64
+        );
+    }
+}
+
+/// Return the winner with perfect play from the initial board state following `ruleset`, for each first player
+///
+/// `None` stands for a draw. Return `Ok(None)` if `database` was not generated for the full game.
+pub fn game_value(
+    database: &StateDatabase,
+    ruleset: &'static Ruleset,
+) -> Result<Option<[Option<usize>; 2]>, DataError> {
+    let mut winners = [None; 2];
+
+    for first_player in [Player::Top, Player::Left] {
+        let id = BoardState::new_game_for_with_ruleset(first_player, ruleset).get_id();
+        if !database.contains(id)? {
+            return Ok(None);
+        }
+
+        winners[usize::from(first_player)] = database.evaluate(id)?;
+    }
+
+    Ok(Some(winners))
+}
+
+/// Print the winner with perfect play from the initial board state following `ruleset`, for each first player
+pub fn print_game_value(database: &StateDatabase, ruleset: &'static Ruleset) {
+    let winners = game_value(database, ruleset)
+        .unwrap_or_else(|e| panic!("{}", e))
+        .unwrap_or_else(|| panic!("Data files were not generated for the full game"));
+
+    for (first_player, winner_opt) in ["Top", "Left"].iter().zip(winners) {
+        let verdict = match winner_opt {
+            Some(0) => "top player wins",
+            Some(_) => "left player wins",
+            None => "draw",
+        };
+        println!("{} player moves first : {}.", first_player, verdict);
+    }
+}
+
+/// Return the number of moves left before the end of the game from each state of `database` won by one of the players
+///
+/// The winner plays the fastest win and the loser the longest defense. States follow `ruleset`.
+/// All states of the database are loaded in memory, so this is meant for data files generated from a subtree.
+pub fn depths_to_win(
+    database: &StateDatabase,
+    ruleset: &'static Ruleset,
+) -> Result<HashMap<u64, u32>, DataError> {
+    let winning_states =
+        [0, 1].map(|player| file_operations::read_states(database.winning_states_path(player)));
+    let [player_0_winning_states, player_1_winning_states] = winning_states;
+    let winning_states = [player_0_winning_states?, player_1_winning_states?];
+
+    let mut depths = HashMap::new();
+    let mut pending: Vec<(BoardState, usize)> = (0..=1)
+        .flat_map(|winner| {
+            winning_states[winner]
+                .iter()
+                .map(move |id| (BoardState::from_id_with_ruleset(id, ruleset), winner))
+        })
+        .collect();
+
+    // States are solved by increasing depth, so each depth is final once found.
+    for depth in 0.. {
+        let is_solved = |(state, winner): &(BoardState, usize)| {
+            if state.is_ended() {
+                return depth == 0;
+            }
+
+            // Depths of the next states, which only count if they are won by the same player.
+            let mut next_depths = state.get_next_states().map(|next_state| {
+                let next_id = next_state.get_id();
+                depths
+                    .get(&next_id)
+                    .copied()
+                    .filter(|_| winning_states[*winner].contains(next_id))
+            });
+            if state.get_next_player() == *winner {
+                next_depths.any(|next_depth| next_depth.is_some_and(|d| d + 1 == depth))
+            } else {
+                let next_depths: Option<Vec<u32>> = next_depths.collect();
+                next_depths.is_some_and(|d| d.into_iter().max() == depth.checked_sub(1))
+            }
+        };
+
+        let (solved, unsolved): (Vec<_>, Vec<_>) = pending.into_iter().partition(is_solved);
+        if solved.is_empty() {
+            break;
+        }
+
+        for (state, _) in solved {
+            depths.insert(state.get_id(), depth);
+        }
+        pending = unsolved;
+    }
+
+    Ok(depths)
+}
+
+/// Return the greatest depth of `depths` (see `depths_to_win`) and the IDs of the states with that depth, in increasing order
+///
+/// Return `None` if `depths` is empty.
+pub fn longest_forced_wins(depths: &HashMap<u64, u32>) -> Option<(u32, Vec<u64>)> {
+    let max_depth = *depths.values().max()?;
+    let mut ids: Vec<u64> = depths
+        .iter()
+        .filter(|&(_, &depth)| depth == max_depth)
+        .map(|(&id, _)| id)
+        .collect();
+    ids.sort_unstable();
+
+    Some((max_depth, ids))
+}
+
+/// Return the moves from `state` to the end of the game with the fastest win and the longest defense, according to `depths`
+///
+/// Each move is given with the resulting state. Return an empty line if `state` is not in `depths`.
+pub fn forced_win_line(depths: &HashMap<u64, u32>, state: &BoardState) -> Vec<(Move, BoardState)> {
+    let mut line = Vec::new();
+    let mut state = state.clone();
+
+    while let Some(&depth) = depths.get(&state.get_id()) {
+        // Both players move to a state one move closer to the end, only the winner having other choices.
+        let Some((next_move, next_state)) =
+            state.movable_moves().into_iter().find_map(|next_move| {
+                let next_state = state.get_next_state_for(next_move)?;
+                depths
+                    .get(&next_state.get_id())
+                    .is_some_and(|&next_depth| next_depth + 1 == depth)
+                    .then_some((next_move, next_state))
+            })
+        else {
+            break;
+        };
+
+        line.push((next_move, next_state.clone()));
+        state = next_state;
+    }
+
+    line
+}
+
+/// Return the moves from `state` to the end of the game with the longest defense of its next player, who loses according to `database`
+///
+/// The opponent plays the fastest win. Each move is given with the resulting state. Return an empty
+/// line if the next player of `state` doesn't lose. Depths are computed with `depths_to_win`, so this is meant for data
+/// files generated from a subtree.
+pub fn longest_defense(
+    database: &StateDatabase,
+    state: &BoardState,
+) -> Result<Vec<(Move, BoardState)>, DataError> {
+    if database.evaluate(state.get_id())? != Some(1 - state.get_next_player()) {
+        return Ok(Vec::new());
+    }
+
+    let depths = depths_to_win(database, state.get_ruleset())?;
+    Ok(forced_win_line(&depths, state))
+}
+
+/// Print the longest forced win among states of `database` following `ruleset`, with the line leading to the end of the game
+pub fn print_longest_forced_win(database: &StateDatabase, ruleset: &'static Ruleset) {
+    let depths = depths_to_win(database, ruleset).unwrap_or_else(|e| panic!("{}", e));
+    let Some((depth, ids)) = longest_forced_wins(&depths) else {
+        println!("No state is won by a player.");
+        return;
+    };
+
+    println!(
+        "Longest forced win : {} moves, from {} state(s) : {}",
+        depth,
+        ids.len(),
+        ids.iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
+    );
+
+    let state = BoardState::from_id_with_ruleset(ids[0], ruleset);
+    println!("\n{}", state);
+    for (ply, (next_move, next_state)) in forced_win_line(&depths, &state).into_iter().enumerate() {
+        println!(
+            "\nMove {} : {} moves piece {}\n{}",
+            ply + 1,
+            ["Top", "Left"][1 - next_state.get_next_player()],
+            next_move,
+            next_state
+        );
+    }
+}
+
+/// Error that occurred while writing the results of an analysis
+#[derive(Debug)]
+pub enum AnalysisError {
+    /// A data file could not be read
+    DataError(DataError),
+
+    /// The input could not be read, or the results could not be written
+    IoError(io::Error),
+}
+
+impl From<DataError> for AnalysisError {
+    fn from(error: DataError) -> Self {
+        Self::DataError(error)
+    }
+}
+
+impl From<io::Error> for AnalysisError {
+    fn from(error: io::Error) -> Self {
+        Self::IoError(error)
+    }
+}
+
+impl fmt::Display for AnalysisError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DataError(error) => error.fmt(f),
+            Self::IoError(error) => error.fmt(f),
+        }
+    }
+}
+
+impl error::Error for AnalysisError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::DataError(error) => Some(error),
+            Self::IoError(error) => Some(error),
+        }
+    }
+}
+
+// Number of IDs solved together by `solve_batch`, which is also the number of IDs between two progress reports.
+const BATCH_SIZE: usize = 100_000;
+
+/// Read one board state ID per line from `reader`, and write an `id,verdict` line for each of them to `writer`
+///
+/// The verdict is `top` or `left` for the winning player, `draw`, `unknown` for states absent from `database`,
+/// or `invalid` for lines which aren't IDs. IDs are solved in batches, looking up the IDs of the same chunk together
+/// (see `StateDatabase::verdicts`). Progress is reported to stderr for large inputs. Return the number of lines read.
+pub fn solve_batch(
+    database: &StateDatabase,
+    reader: impl BufRead,
+    writer: &mut impl io::Write,
+) -> Result<u64, AnalysisError> {
+    let mut lines = reader.lines();
+    let mut lines_count = 0;
+
+    loop {
+        let batch: Vec<String> = lines.by_ref().take(BATCH_SIZE).collect::<io::Result<_>>()?;
+        if batch.is_empty() {
+            break;
+        }
+
+        let ids: Vec<Option<u64>> = batch.iter().map(|line| line.trim().parse().ok()).collect();
+        let valid_ids: Vec<u64> = ids.iter().flatten().copied().collect();
+        let mut verdicts = database.verdicts(&valid_ids, 0)?.into_iter();
+
+        for (line, id_opt) in batch.iter().zip(&ids) {
+            let verdict = match id_opt {
+                Some(_) => verdict_name(verdicts.next().expect("Each valid ID has a verdict")),
+                None => "invalid",
+            };
+            writeln!(writer, "{},{}", line.trim(), verdict)?;
+        }
+
+        lines_count += batch.len() as u64;
+        if batch.len() == BATCH_SIZE {
+            eprintln!("{} IDs solved.", lines_count);
+        }
+    }
+
+    writer.flush()?;
+    Ok(lines_count)
+}
+
+/// Write to `writer` a CSV table of the states of `database` following `ruleset`, stopping after `limit` states if provided
+///
+/// Each row holds the ID of a state, its next player, the number of pieces home for each player and the winner
+/// (`top`, `left` or `draw`). All states are read one chunk at a time. Return the number of written rows, without the header.
+pub fn export_csv(
+    database: &StateDatabase,
+    ruleset: &'static Ruleset,
+    limit: Option<usize>,
+    writer: &mut impl io::Write,
+) -> Result<u64, AnalysisError> {
+    let ids = list_ids(database.all_states_path())?;
+    let mut rows = 0;
+
+    writeln!(writer, "id,next_player,pieces_home_0,pieces_home_1,verdict")?;
+    for id in ids.take(limit.unwrap_or(usize::MAX)) {
+        let id = id?;
+        let state = BoardState::from_id_with_ruleset(id, ruleset);
+
+        writeln!(
+            writer,
+            "{},{},{},{},{}",
+            id,
+            state.get_next_player(),
+            state.finished_pieces(0),
+            state.finished_pieces(1),
+            winner_name(database, id)?
+        )?;
+        rows += 1;
+    }
+
+    writer.flush()?;
+    Ok(rows)
+}
+
+/// Return the name of the player who wins from the board state `id` according to `database`, `draw` or `unknown`
+fn winner_name(database: &StateDatabase, id: u64) -> Result<&'static str, DataError> {
+    Ok(verdict_name(database.verdict(id, 0)?))
+}
+
+/// Return the name of the player who wins given `verdict`, the verdict of the top player, `draw` or `unknown`
+fn verdict_name(verdict: BoardStateEval) -> &'static str {
+    match verdict {
+        BoardStateEval::Win => "top",
+        BoardStateEval::Loss => "left",
+        BoardStateEval::Draw => "draw",
+        BoardStateEval::Unknown => "unknown",
+    }
+}
+
+/// Return the first board state reachable from `root` which is missing from `database`, in breadth-first order
+///
+/// Return `Ok(None)` if `database` contains all states reachable from `root`, as it does if it was generated from `root`.
+pub fn first_missing_state(
+    database: &StateDatabase,
+    root: &BoardState,
+) -> Result<Option<u64>, DataError> {
+    let mut seen_ids = RoaringTreemap::new();
+    seen_ids.insert(root.get_id());
+    let mut queue = VecDeque::from([root.clone()]);
+
+    while let Some(state) = queue.pop_front() {
+        if !database.contains(state.get_id())? {
+            return Ok(Some(state.get_id()));
+        }
+
+        if state.is_ended() {
+            continue;
+        }
+
+        for next_state in state.get_next_states() {
+            if seen_ids.insert(next_state.get_id()) {
+                queue.push_back(next_state);
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Return the game tree of the board states reached from `root` in at most `max_depth` moves, in the DOT language of Graphviz
+///
+/// Nodes are board state IDs colored by winner according to `database` (blue for the top player, red for the left player,
+/// gray for draws), and edges are labeled with the moved piece. Ended states are drawn as boxes.
+/// Nodes of states which are not in `database` are left white.
+pub fn game_tree_dot(
+    database: &StateDatabase,
+    root: &BoardState,
+    max_depth: u32,
+) -> Result<String, DataError> {
+    let mut dot = String::from("digraph game_tree {\n");
+    let mut seen_ids = HashSet::from([root.get_id()]);
+    let mut queue = VecDeque::from([(root.clone(), 0)]);
+
+    while let Some((state, depth)) = queue.pop_front() {
+        let id = state.get_id();
+        let color = if !database.contains(id)? {
+            "white"
+        } else {
+            match database.evaluate(id)? {
+                Some(0) => "lightblue",
+                Some(_) => "lightcoral",
+                None => "lightgray",
+            }
+        };
+        let shape = if state.is_ended() { "box" } else { "ellipse" };
+        writeln!(
+            dot,
+            "  {} [style=filled, fillcolor={}, shape={}];",
+            id, color, shape
+        )
+        .expect("Writing to a string can't fail");
+
+        if depth == max_depth || state.is_ended() {
+            continue;
+        }
+
+        for piece in state.movable_labels() {
+            let next_state = state
+                .get_next_state(piece)
+                .expect("Movable pieces have a next state");
+            writeln!(
+                dot,
+                "  {} -> {} [label=\"{}\"];",
+                id,
+                next_state.get_id(),
+                piece
+            )
+            .expect("Writing to a string can't fail");
+
+            if seen_ids.insert(next_state.get_id()) {
+                queue.push_back((next_state, depth + 1));
+            }
+        }
+    }
+
+    dot.push_str("}\n");
+    Ok(dot)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::slice;
+
+    use crate::file_operations::{create_new_file, write_states};
+    use crate::generate::tests::generate_in;
+
+    use super::*;
+
+    #[test]
+    fn data_files_diff() {
+        let states_a = RoaringTreemap::from_sorted_iter([1, 5, 8, 1 << 40]).unwrap();
+        let states_b = RoaringTreemap::from_sorted_iter([5, 8, 9]).unwrap();
+
+        file_operations::tests::with_tempdir(|dir| {
+            let path_a = file_operations::data_file_path(dir, "", "a");
+            let path_b = file_operations::data_file_path(dir, "", "b");
+            let path_c = file_operations::data_file_path(dir, "", "c");
+
+            write_states(
+                create_new_file(&path_a),
+                &path_a,
+                &states_a,
+                DataFormat::Zip,
+            )
+            .unwrap();
+            write_states(
+                create_new_file(&path_b),
+                &path_b,
+                &states_b,
+                DataFormat::Gzip,
+            )
+            .unwrap();
+
+            let (only_a, only_b) = diff(&path_a, &path_b).unwrap();
+            assert_eq!(only_a.iter().collect::<Vec<u64>>(), vec![1, 1 << 40]);
+            assert_eq!(only_b.iter().collect::<Vec<u64>>(), vec![9]);
+
+            let (only_a, only_b) = diff(&path_a, &path_a).unwrap();
+            assert!(only_a.is_empty() && only_b.is_empty());
+
+            assert!(matches!(
+                diff(&path_a, &path_c),
+                Err(DataError::IoError { .. })
+            ));
+
+            let ids: Vec<u64> = list_ids(&path_a).unwrap().map(Result::unwrap).collect();
+            assert_eq!(ids, states_a.iter().collect::<Vec<u64>>());
+            assert!(list_ids(&path_c).is_err());
+        });
+    }
+
+    #[test]
+    fn data_files_merge() {
+        let states_a = RoaringTreemap::from_sorted_iter([1, 5, 1 << 40]).unwrap();
+        let states_b = RoaringTreemap::from_sorted_iter([5, 9]).unwrap();
+
+        file_operations::tests::with_tempdir(|dir| {
+            let path_a = file_operations::data_file_path(dir, "", "a");
+            let path_b = file_operations::data_file_path(dir, "", "b");
+            let path_c = file_operations::data_file_path(dir, "", "c");
+            let path_ab = file_operations::data_file_path(dir, "", "ab");
+            let path_ac = file_operations::data_file_path(dir, "", "ac");
+
+            write_states(
+                create_new_file(&path_a),
+                &path_a,
+                &states_a,
+                DataFormat::Zip,
+            )
+            .unwrap();
+            write_states(
+                create_new_file(&path_b),
+                &path_b,
+                &states_b,
+                DataFormat::Roaring,
+            )
+            .unwrap();
+
+            let inputs = [path_a.clone(), path_b.clone()];
+            assert_eq!(merge(&inputs, &path_ab, DataFormat::Gzip).unwrap(), 4);
+            assert_eq!(
+                file_operations::read_states(&path_ab).unwrap(),
+                &states_a | &states_b
+            );
+
+            // The output file is never overwritten.
+            assert!(std::panic::catch_unwind(|| merge(&inputs, &path_a, DataFormat::Zip)).is_err());
+            assert_eq!(file_operations::read_states(&path_a).unwrap(), states_a);
+
+            // Nothing is written if an input file can't be read.
+            let inputs = [path_a.clone(), path_c.clone()];
+            assert!(matches!(
+                merge(&inputs, &path_ac, DataFormat::Zip),
+                Err(DataError::IoError { .. })
+            ));
+            assert!(!std::path::Path::new(&path_ac).exists());
+        });
+    }
+
+    #[test]
+    fn branching() {
+        let root = BoardState::from(85065666045);
+
+        file_operations::tests::with_tempdir(|dir| {
+            generate_in(dir, slice::from_ref(&root));
+            let path = StateDatabase::new(dir).all_states_path().to_string();
+
+            let histogram = branching_histogram(&path, &Ruleset::OFFICIAL).unwrap();
+            let all_states = file_operations::read_states(&path).unwrap();
+            assert_eq!(histogram.iter().sum::<u64>(), all_states.len());
+
+            let mut expected_histogram = [0; 6];
+            for id in &all_states {
+                expected_histogram[BoardState::from(id).movable_labels().len()] += 1;
+            }
+            assert_eq!(histogram, expected_histogram);
+
+            // Some states of the subtree are ended.
+            assert!(histogram[0] > 0);
+
+            let missing = dir.join("missing");
+            assert!(branching_histogram(missing.to_str().unwrap(), &Ruleset::OFFICIAL).is_err());
+        });
+    }
+
+    #[test]
+    fn dot_game_tree() {
+        let root = BoardState::from(85065666045);
+
+        file_operations::tests::with_tempdir(|dir| {
+            generate_in(dir, slice::from_ref(&root));
+            let database = StateDatabase::new(dir);
+
+            let dot = game_tree_dot(&database, &root, 0).unwrap();
+            assert_eq!(
+                dot,
+                "digraph game_tree {\n  85065666045 [style=filled, fillcolor=lightcoral, shape=ellipse];\n}\n"
+            );
+
+            // Each state reached in one move has a node and an edge labeled with the moved piece.
+            let dot = game_tree_dot(&database, &root, 1).unwrap();
+            for piece in root.movable_labels() {
+                let next_id = root.get_next_state(piece).unwrap().get_id();
+                assert!(dot.contains(&format!(
+                    "  85065666045 -> {} [label=\"{}\"];\n",
+                    next_id, piece
+                )));
+                assert!(dot.contains(&format!("  {} [style=filled", next_id)));
+            }
+            assert_eq!(dot.matches(" -> ").count(), root.movable_labels().len());
+
+            // States outside of the database are not colored.
+            let dot = game_tree_dot(&database, &BoardState::new_game(0), 0).unwrap();
+            assert!(dot.contains("fillcolor=white"));
+        });
+    }
+
+    #[test]
+    fn batch_verdicts() {
+        let root = BoardState::from(85065666045);
+
+        file_operations::tests::with_tempdir(|dir| {
+            generate_in(dir, slice::from_ref(&root));
+            let database = StateDatabase::new(dir);
+
+            let next_id = root.get_next_state(0).unwrap().get_id();
+            let input = format!("85065666045\n {} \n1\nabc\n\n", next_id);
+            let mut output = Vec::new();
+            assert_eq!(
+                solve_batch(&database, input.as_bytes(), &mut output).unwrap(),
+                5
+            );
+            assert_eq!(
+                String::from_utf8(output).unwrap(),
+                format!(
+                    "85065666045,left\n{},top\n1,unknown\nabc,invalid\n,invalid\n",
+                    next_id
+                )
+            );
+
+            // Unreadable data files are reported instead of panicking.
+            let missing = StateDatabase::new(dir.join("missing"));
+            assert!(matches!(
+                solve_batch(&missing, input.as_bytes(), &mut Vec::new()),
+                Err(AnalysisError::DataError(_))
+            ));
+        });
+    }
+
+    #[test]
+    fn csv_export() {
+        let root = BoardState::from(85065666045);
+
+        file_operations::tests::with_tempdir(|dir| {
+            generate_in(dir, slice::from_ref(&root));
+            let database = StateDatabase::new(dir);
+            let all_states = file_operations::read_states(database.all_states_path()).unwrap();
+
+            let mut output = Vec::new();
+            let rows = export_csv(&database, &Ruleset::OFFICIAL, None, &mut output).unwrap();
+            assert_eq!(rows, all_states.len());
+
+            let output = String::from_utf8(output).unwrap();
+            let mut lines = output.lines();
+            assert_eq!(
+                lines.next().unwrap(),
+                "id,next_player,pieces_home_0,pieces_home_1,verdict"
+            );
+            for (line, id) in lines.zip(&all_states) {
+                let state = BoardState::from(id);
+                let fields: Vec<&str> = line.split(',').collect();
+                assert_eq!(fields[0], id.to_string());
+                assert_eq!(fields[1], state.get_next_player().to_string());
+                assert_eq!(fields[2], state.finished_pieces(0).to_string());
+                assert_eq!(fields[3], state.finished_pieces(1).to_string());
+                let verdict = match database.evaluate(id).unwrap() {
+                    Some(0) => "top",
+                    Some(_) => "left",
+                    None => "draw",
+                };
+                assert_eq!(fields[4], verdict);
+            }
+            assert!(output.contains(",left\n") && output.contains(",top\n"));
+
+            let mut output = Vec::new();
+            assert_eq!(
+                export_csv(&database, &Ruleset::OFFICIAL, Some(3), &mut output).unwrap(),
+                3
+            );
+            assert_eq!(String::from_utf8(output).unwrap().lines().count(), 4);
+
+            // Unreadable data files are reported instead of panicking.
+            let missing = StateDatabase::new(dir.join("missing"));
+            assert!(matches!(
+                export_csv(&missing, &Ruleset::OFFICIAL, None, &mut Vec::new()),
+                Err(AnalysisError::DataError(_))
+            ));
+        });
+    }
+
+    #[test]
+    fn longest_win() {
+        let root = BoardState::from(85065666045);
+
+        file_operations::tests::with_tempdir(|dir| {
+            let database = StateDatabase::new(dir);
+            assert!(depths_to_win(&database, &Ruleset::OFFICIAL).is_err());
+
+            generate_in(dir, slice::from_ref(&root));
+            let depths = depths_to_win(&database, &Ruleset::OFFICIAL).unwrap();
+            let all_states = file_operations::read_states(database.all_states_path()).unwrap();
+
+            // The subtree has no draws, so every state has a depth.
+            assert_eq!(depths.len() as u64, all_states.len());
+            for (&id, &depth) in &depths {
+                let state = BoardState::from(id);
+                if state.is_ended() {
+                    assert_eq!(depth, 0);
+                    continue;
+                }
+
+                let winner = database.evaluate(id).unwrap().unwrap();
+                let next_depths = state.get_next_states().filter_map(|next_state| {
+                    let next_id = next_state.get_id();
+                    (database.evaluate(next_id).unwrap() == Some(winner)).then(|| depths[&next_id])
+                });
+                let expected = if state.get_next_player() == winner {
+                    next_depths.min()
+                } else {
+                    next_depths.max()
+                };
+                assert_eq!(Some(depth), expected.map(|d| d + 1));
+            }
+
+            let (max_depth, ids) = longest_forced_wins(&depths).unwrap();
+            assert!(max_depth > 1);
+            assert!(ids.iter().all(|id| depths[id] == max_depth));
+            assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+            assert!(depths.values().all(|&depth| depth <= max_depth));
+
+            // The line reaches the end of the game in as many moves as the depth.
+            let state = BoardState::from(ids[0]);
+            let line = forced_win_line(&depths, &state);
+            assert_eq!(line.len() as u32, max_depth);
+            assert!(line.last().unwrap().1.is_ended());
+            assert_eq!(
+                1 - line.last().unwrap().1.get_next_player(),
+                database.evaluate(ids[0]).unwrap().unwrap()
+            );
+            let mut previous_state = state;
+            for (next_move, next_state) in line {
+                assert_eq!(
+                    previous_state
+                        .get_next_state_for(next_move)
+                        .unwrap()
+                        .get_id(),
+                    next_state.get_id()
+                );
+                previous_state = next_state;
+            }
+
+            assert_eq!(longest_forced_wins(&HashMap::new()), None);
+            assert!(forced_win_line(&depths, &BoardState::new_game(0)).is_empty());
+        });
+    }
+
+    #[test]
+    fn longest_defenses() {
+        let root = BoardState::from(85065666045);
+
+        file_operations::tests::with_tempdir(|dir| {
+            let database = StateDatabase::new(dir);
+            generate_in(dir, slice::from_ref(&root));
+            let depths = depths_to_win(&database, &Ruleset::OFFICIAL).unwrap();
+
+            // The root is won by its next player, who has no defense to play.
+            assert_eq!(database.evaluate(root.get_id()).unwrap(), Some(1));
+            assert!(longest_defense(&database, &root).unwrap().is_empty());
+
+            // After the fastest win starts, the loser delays the end as long as possible.
+            let (next_move, lost_state) = forced_win_line(&depths, &root).remove(0);
+            let line = longest_defense(&database, &lost_state).unwrap();
+            assert_eq!(line.len() as u32, depths[&lost_state.get_id()]);
+            assert_eq!(line.len() as u32 + 1, depths[&root.get_id()]);
+            assert_eq!(line[0].1.get_next_player(), 1);
+            assert!(line.last().unwrap().1.is_ended());
+
+            let longest_next_depth = lost_state
+                .get_next_states()
+                .map(|next_state| depths[&next_state.get_id()])
+                .max()
+                .unwrap();
+            assert_eq!(depths[&line[0].1.get_id()], longest_next_depth);
+            assert_eq!(
+                root.get_next_state_for(next_move).unwrap().get_id(),
+                lost_state.get_id()
+            );
+        });
+    }
+
+    #[test]
+    fn closure() {
+        let root = BoardState::from(85065666045);
+
+        file_operations::tests::with_tempdir(|dir| {
+            let database = StateDatabase::new(dir);
+            assert!(first_missing_state(&database, &root).is_err());
+
+            generate_in(dir, slice::from_ref(&root));
+            assert_eq!(first_missing_state(&database, &root).unwrap(), None);
+            let next_state = root.get_next_state(0).unwrap();
+            assert_eq!(first_missing_state(&database, &next_state).unwrap(), None);
+
+            // Data files generated from a later state don't cover the initial state.
+            let init_state = BoardState::new_game(1);
+            assert_eq!(
+                first_missing_state(&database, &init_state).unwrap(),
+                Some(init_state.get_id())
+            );
+        });
+
+        file_operations::tests::with_tempdir(|dir| {
+            // The next states of `root` are missing, the first one being reached by moving its first movable piece.
+            let states = RoaringTreemap::from_iter([root.get_id()]);
+            let database = StateDatabase::new(dir);
+            let path = database.all_states_path();
+            write_states(create_new_file(path), path, &states, DataFormat::Zip).unwrap();
+
+            let first_piece = root.movable_labels()[0];
+            assert_eq!(
+                first_missing_state(&database, &root).unwrap(),
+                Some(root.get_next_state(first_piece).unwrap().get_id())
+            );
+        });
+    }
+
+    #[test]
+    fn start_game_value() {
+        file_operations::tests::with_tempdir(|dir| {
+            let database = StateDatabase::new(dir);
+            assert!(game_value(&database, &Ruleset::OFFICIAL).is_err());
+
+            // Data files generated from a later board state don't cover the initial ones.
+            generate_in(dir, slice::from_ref(&BoardState::from(85065666045)));
+            assert_eq!(game_value(&database, &Ruleset::OFFICIAL).unwrap(), None);
+        });
+    }
+}
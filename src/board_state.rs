@@ -1,49 +1,392 @@
-use std::{fmt, iter};
-
-// Regular piece progression given [player][piece][piece's position].
-const REGULAR_MOVES: [[[usize; 13]; 5]; 2] = [
-    [
-        [1, 1, 1, 1, 1, 1, 3, 0, 3, 3, 2, 1, 0],
-        [3, 0, 3, 3, 2, 1, 1, 1, 1, 1, 1, 1, 0],
-        [2, 0, 2, 2, 2, 1, 2, 0, 2, 2, 2, 1, 0],
-        [3, 0, 3, 3, 2, 1, 1, 1, 1, 1, 1, 1, 0],
-        [1, 1, 1, 1, 1, 1, 3, 0, 3, 3, 2, 1, 0],
-    ],
-    [
-        [3, 0, 3, 3, 2, 1, 1, 1, 1, 1, 1, 1, 0],
-        [1, 1, 1, 1, 1, 1, 3, 0, 3, 3, 2, 1, 0],
-        [2, 0, 2, 2, 2, 1, 2, 0, 2, 2, 2, 1, 0],
-        [1, 1, 1, 1, 1, 1, 3, 0, 3, 3, 2, 1, 0],
-        [3, 0, 3, 3, 2, 1, 1, 1, 1, 1, 1, 1, 0],
-    ],
-];
-
-// Initial regular piece progression given [player][piece].
-const FIRST_MOVES: [[usize; 5]; 2] = [[1, 3, 2, 3, 1], [3, 1, 2, 1, 3]];
-
-// ID is built from the positions of pieces, alternating between
-// the two players, and ends with the number of the next player.
-const ID_PART_SIZE: [u64; 11] = [12, 12, 12, 12, 11, 11, 12, 12, 12, 12, 2];
-
-// Factor by which each ID part is multiplied.
-const ID_PART_FACTOR: [u64; 11] = [
-    8671297536, 722608128, 60217344, 5018112, 456192, 41472, 3456, 288, 24, 2, 1,
-];
+use std::hash::{Hash, Hasher};
+use std::{error, fmt, iter, ptr};
+
+/// Rules of the game, given by the speed of every piece on its way out and the number of pieces needed to win
+///
+/// On its way back, the speed of a piece is 4 minus its speed on the way out.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Ruleset {
+    // Initial regular piece progression given [player][piece].
+    first_moves: [[usize; 5]; 2],
+
+    // Regular piece progression given [player][piece][piece's position].
+    regular_moves: [[[usize; 13]; 5]; 2],
+
+    // ID is built from the positions of pieces, alternating between
+    // the two players, and ends with the number of the next player.
+    id_part_size: [u64; 11],
+
+    // Factor by which each ID part is multiplied.
+    id_part_factor: [u64; 11],
+
+    // Number of pieces a player must bring back to their final position to win.
+    goal: usize,
+}
+
+impl Ruleset {
+    /// Official rules of the game
+    pub const OFFICIAL: Self = Self::new([[1, 3, 2, 3, 1], [3, 1, 2, 1, 3]]);
+
+    /// House rule in which the pieces of both players have the speeds of the top player's pieces
+    pub const IDENTICAL_FIRST_MOVES: Self = Self::new([[1, 3, 2, 3, 1], [1, 3, 2, 3, 1]]);
+
+    /// Number of pieces a player must bring back to their final position to win, in the official rules
+    pub const DEFAULT_GOAL: usize = 4;
+
+    /// Create rules from the speed (1, 2 or 3) of each piece on its way out, given [player][piece]
+    ///
+    /// The goal is `DEFAULT_GOAL`, see `with_goal` to change it.
+    pub const fn new(first_moves: [[usize; 5]; 2]) -> Self {
+        let mut regular_moves = [[[0; 13]; 5]; 2];
+        let mut id_part_size = [2; 11];
+
+        let mut player = 0;
+        while player < 2 {
+            let mut piece = 0;
+            while piece < 5 {
+                let first_move = first_moves[player][piece];
+                assert!(
+                    first_move >= 1 && first_move <= 3,
+                    "Speeds must be 1, 2 or 3"
+                );
+
+                let mut position = 0;
+                while position < BoardState::HOME_POSITION {
+                    // Position 1 can only be reached by a piece moving 1 square at
+                    // a time on its way out, position 7 by a piece moving 1 square
+                    // at a time on its way back.
+                    let reachable =
+                        (position != 1 || first_move == 1) && (position != 7 || first_move == 3);
+
+                    if reachable {
+                        // A piece always stops on the opposite side and on its final position.
+                        regular_moves[player][piece][position] =
+                            if position < BoardState::FAR_SIDE_POSITION {
+                                min(first_move, BoardState::FAR_SIDE_POSITION - position)
+                            } else {
+                                min(4 - first_move, BoardState::HOME_POSITION - position)
+                            };
+                    }
+
+                    position += 1;
+                }
+
+                id_part_size[piece * 2 + player] = 13
+                    - (first_move != 1) as u64 // Position 1 is unreachable.
+                    - (first_move != 3) as u64; // Position 7 is unreachable.
+
+                piece += 1;
+            }
+            player += 1;
+        }
+
+        let mut id_part_factor = [1; 11];
+        let mut index = 10;
+        while index > 0 {
+            id_part_factor[index - 1] = id_part_factor[index] * id_part_size[index];
+            index -= 1;
+        }
+
+        Self {
+            first_moves,
+            regular_moves,
+            id_part_size,
+            id_part_factor,
+            goal: Self::DEFAULT_GOAL,
+        }
+    }
+
+    /// Return the same rules, except that the first player to bring `goal` (1 to 5) pieces back wins
+    pub const fn with_goal(self, goal: usize) -> Self {
+        assert!(goal >= 1 && goal <= 5, "The goal must be between 1 and 5");
+
+        Self { goal, ..self }
+    }
+
+    /// Return the number of pieces a player must bring back to their final position to win
+    pub fn get_goal(&self) -> usize {
+        self.goal
+    }
+}
+
+/// Return the smallest of `a` and `b` (`std::cmp::min` can't be used in constants)
+const fn min(a: usize, b: usize) -> usize {
+    if a < b {
+        a
+    } else {
+        b
+    }
+}
+
+/// Player of the game, whose number is used in board states
+#[repr(usize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Player {
+    /// Top player
+    Top = 0,
+
+    /// Left player
+    Left = 1,
+}
+
+impl Player {
+    /// Return a player selected at random
+    pub fn random() -> Self {
+        if fastrand::bool() {
+            Player::Left
+        } else {
+            Player::Top
+        }
+    }
+}
+
+impl From<Player> for usize {
+    /// Return the number of `player` : 0 for the top player and 1 for the left player
+    fn from(player: Player) -> Self {
+        player as usize
+    }
+}
+
+/// Move of one of the next player's pieces, whose number is always valid
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Move(usize);
+
+impl Move {
+    /// Return the move of `piece`, or `None` if `piece` is not a piece number (0 to 4)
+    pub fn new(piece: usize) -> Option<Self> {
+        (piece < 5).then_some(Move(piece))
+    }
+
+    /// Return the number of the moved piece
+    pub fn piece(self) -> usize {
+        self.0
+    }
+}
+
+impl From<Move> for usize {
+    /// Return the number of the piece moved by `next_move`
+    fn from(next_move: Move) -> Self {
+        next_move.0
+    }
+}
+
+impl fmt::Display for Move {
+    /// Display the number of the moved piece
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Phase of the game
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Fewer than 3 pieces have reached the opposite side
+    Opening,
+
+    /// Neither opening nor endgame
+    Midgame,
+
+    /// At least one player has 3 pieces in their final position
+    Endgame,
+}
+
+/// Error returned when a board notation can't be parsed (see `BoardState::from_notation`)
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// The notation doesn't follow the `T:<positions> L:<positions> next=<T or L>` layout
+    InvalidLayout { notation: String },
+
+    /// `player` is given `count` positions instead of one per piece
+    WrongPieceCount { player: usize, count: usize },
+
+    /// The position of `piece` belonging to `player` is not a number from 0 to 12
+    InvalidPosition {
+        player: usize,
+        piece: usize,
+        position: String,
+    },
+
+    /// `piece` belonging to `player` can never stop at `position`, given its speed
+    UnreachablePosition {
+        player: usize,
+        piece: usize,
+        position: usize,
+    },
+
+    /// The positions can't occur in a game, e.g. two pieces share a square (see `BoardState::is_well_formed`)
+    Malformed,
+}
 
 /// State of the game board, including next player and position of pieces
+///
+/// Board states are equal when they have the same ID and follow the same rules.
 #[derive(Clone)]
 pub struct BoardState {
     id: u64,
+    ruleset: &'static Ruleset,
+}
+
+impl PartialEq for BoardState {
+    fn eq(&self, other: &Self) -> bool {
+        // Rules are usually the same static value, which is much cheaper to compare by address.
+        self.id == other.id
+            && (ptr::eq(self.ruleset, other.ruleset) || self.ruleset == other.ruleset)
+    }
+}
+
+impl Eq for BoardState {}
+
+impl Hash for BoardState {
+    /// Hash the ID only, which is consistent with `eq` since equal board states have the same ID
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
 }
 
 impl BoardState {
-    /// Create a new game starting with `first_player`
+    /// Position of a piece on the opposite side of the board, where it turns around
+    pub const FAR_SIDE_POSITION: usize = 6;
+
+    /// Position of a piece back home, after which it can't move anymore
+    pub const HOME_POSITION: usize = 12;
+
+    /// Number of columns taken by the board as displayed on a terminal (see `render_compact` for narrower terminals)
+    pub const DISPLAY_WIDTH: usize = 32;
+
+    /// Create a new game starting with `first_player`, following the official rules
     pub fn new_game(first_player: usize) -> Self {
-        let mut state = Self { id: 0 };
+        Self::new_game_with_ruleset(first_player, &Ruleset::OFFICIAL)
+    }
+
+    /// Same as `new_game`, with a typed `first_player`
+    pub fn new_game_for(first_player: Player) -> Self {
+        Self::new_game(first_player.into())
+    }
+
+    /// Same as `new_game_with_ruleset`, with a typed `first_player`
+    pub fn new_game_for_with_ruleset(first_player: Player, ruleset: &'static Ruleset) -> Self {
+        Self::new_game_with_ruleset(first_player.into(), ruleset)
+    }
+
+    /// Create a new game starting with `first_player`, following `ruleset`
+    pub fn new_game_with_ruleset(first_player: usize, ruleset: &'static Ruleset) -> Self {
+        let mut state = Self::from_id_with_ruleset(0, ruleset);
         state.set_next_player(first_player);
         state
     }
 
+    /// Create a board state from its ID, which depends on `ruleset`
+    pub fn from_id_with_ruleset(id: u64, ruleset: &'static Ruleset) -> Self {
+        Self { id, ruleset }
+    }
+
+    /// Create a board state from its `notation`, following the official rules
+    ///
+    /// The notation gives the positions (0 to 12) of the pieces of each player, then the next player,
+    /// e.g. `T:0,6,12,9,9 L:7,1,12,1,6 next=L`. The board state must be well-formed.
+    pub fn from_notation(notation: &str) -> Result<Self, ParseError> {
+        Self::from_notation_with_ruleset(notation, &Ruleset::OFFICIAL)
+    }
+
+    /// Same as `from_notation`, following `ruleset`
+    pub fn from_notation_with_ruleset(
+        notation: &str,
+        ruleset: &'static Ruleset,
+    ) -> Result<Self, ParseError> {
+        let invalid_layout = || ParseError::InvalidLayout {
+            notation: notation.to_string(),
+        };
+
+        let fields: Vec<&str> = notation.split_whitespace().collect();
+        let [top_positions, left_positions, next_player] = fields[..] else {
+            return Err(invalid_layout());
+        };
+        let next_player = match next_player {
+            "next=T" => 0,
+            "next=L" => 1,
+            _ => return Err(invalid_layout()),
+        };
+
+        let mut state = Self::new_game_with_ruleset(next_player, ruleset);
+
+        for (player, (positions, prefix)) in [(top_positions, "T:"), (left_positions, "L:")]
+            .into_iter()
+            .enumerate()
+        {
+            let positions: Vec<&str> = positions
+                .strip_prefix(prefix)
+                .ok_or_else(invalid_layout)?
+                .split(',')
+                .collect();
+            if positions.len() != 5 {
+                return Err(ParseError::WrongPieceCount {
+                    player,
+                    count: positions.len(),
+                });
+            }
+
+            for (piece, position) in positions.into_iter().enumerate() {
+                let position = position
+                    .parse()
+                    .ok()
+                    .filter(|&position| position <= Self::HOME_POSITION)
+                    .ok_or_else(|| ParseError::InvalidPosition {
+                        player,
+                        piece,
+                        position: position.to_string(),
+                    })?;
+
+                // Positions a piece can't stop at aren't stored in the ID, so they would be read back differently.
+                state.set_piece_position(player, piece, position);
+                if state.get_piece_position(player, piece) != position {
+                    return Err(ParseError::UnreachablePosition {
+                        player,
+                        piece,
+                        position,
+                    });
+                }
+            }
+        }
+
+        if !state.is_well_formed() {
+            return Err(ParseError::Malformed);
+        }
+
+        Ok(state)
+    }
+
+    /// Create a board state following the official rules, with `next_player` and the `positions` of pieces given [player][piece]
+    ///
+    /// Nothing is checked : positions must be reachable by their piece (e.g. a piece moving 3 squares can't be at
+    /// position 1), and the result may not be well-formed. Only available in tests and with the `test-fixtures` feature.
+    #[cfg(any(test, feature = "test-fixtures"))]
+    pub fn from_positions_unchecked(next_player: usize, positions: [[usize; 5]; 2]) -> Self {
+        let mut state = Self::new_game(next_player);
+        for (player, player_positions) in positions.iter().enumerate() {
+            for (piece, &position) in player_positions.iter().enumerate() {
+                state.set_piece_position(player, piece, position);
+            }
+        }
+        state
+    }
+
+    /// Return the largest ID representing a board state, following the official rules
+    ///
+    /// All parts of that ID are at their maximum. Many smaller IDs are not reachable in a game, see `is_well_formed`.
+    pub fn max_valid_id() -> u64 {
+        Self::max_valid_id_with_ruleset(&Ruleset::OFFICIAL)
+    }
+
+    /// Return the largest ID representing a board state, following `ruleset`
+    pub fn max_valid_id_with_ruleset(ruleset: &'static Ruleset) -> u64 {
+        ruleset.id_part_factor[0] * ruleset.id_part_size[0] - 1
+    }
+
+    /// Return the rules followed by this board state
+    pub fn get_ruleset(&self) -> &'static Ruleset {
+        self.ruleset
+    }
+
     /// Return the ID representing this board state
     pub fn get_id(&self) -> u64 {
         self.id
@@ -51,13 +394,19 @@ impl BoardState {
 
     /// Return the ID part at the given `index`
     fn get_id_part(&self, index: usize) -> u64 {
-        (self.id / ID_PART_FACTOR[index]) % ID_PART_SIZE[index]
+        (self.id / self.ruleset.id_part_factor[index]) % self.ruleset.id_part_size[index]
     }
 
     /// Update the ID part at the given `index`
     fn set_id_part(&mut self, index: usize, value: u64) {
-        let id_part_factor = ID_PART_FACTOR[index];
-        self.id = self.id - (id_part_factor * self.get_id_part(index)) + (id_part_factor * value);
+        let id_part_factor = self.ruleset.id_part_factor[index];
+
+        // Wrapping arithmetic only matters for IDs beyond the largest valid ID,
+        // which don't represent any board state but must not cause a panic.
+        self.id = self
+            .id
+            .wrapping_sub(id_part_factor * self.get_id_part(index))
+            .wrapping_add(id_part_factor * value);
     }
 
     /// Return the number of the next player
@@ -77,6 +426,13 @@ impl BoardState {
         self.id ^= 1;
     }
 
+    /// Return the same board state with the other player to move, as if the next player passed
+    pub fn passed(&self) -> Self {
+        let mut state = self.clone();
+        state.switch_next_player();
+        state
+    }
+
     /// Return position of `piece` belonging to `player`
     fn get_piece_position(&self, player: usize, piece: usize) -> usize {
         let mut position = self.get_id_part(piece * 2 + player) as usize;
@@ -85,7 +441,7 @@ impl BoardState {
         // The actual position must therefore be calculated by adding 1 for each
         // unreachable position.
         if position > 0 {
-            let first_move = FIRST_MOVES[player][piece];
+            let first_move = self.ruleset.first_moves[player][piece];
 
             if first_move != 1 {
                 position += 1;
@@ -107,7 +463,7 @@ impl BoardState {
         // This is done by taking the actual position and subtracting 1 for each
         // unreachable position.
         if position > 1 {
-            let first_move = FIRST_MOVES[player][piece];
+            let first_move = self.ruleset.first_moves[player][piece];
 
             if position > 7 && first_move != 3 {
                 position -= 1;
@@ -122,23 +478,145 @@ impl BoardState {
     }
 
     /// Is the game over?
+    ///
+    /// The game ends when the last player has brought back as many pieces as the goal of the rules.
     pub fn is_ended(&self) -> bool {
-        let last_player = 1 - self.get_next_player();
-        let mut movable_pieces: u8 = 0;
+        self.finished_pieces(1 - self.get_next_player()) >= self.ruleset.goal
+    }
+
+    /// Return the number of pieces of `player` in their final position
+    pub fn finished_pieces(&self, player: usize) -> usize {
+        (0..5)
+            .filter(|&piece| self.get_piece_position(player, piece) == Self::HOME_POSITION)
+            .count()
+    }
 
+    /// Does the ID represent a board state which can occur in a game, regardless of data files?
+    ///
+    /// The ID must be in range, no two pieces can share a square, and the next player can't have already finished.
+    pub fn is_well_formed(&self) -> bool {
+        if self.id > Self::max_valid_id_with_ruleset(self.ruleset) {
+            return false;
+        }
+
+        // Pieces of the top player are checked against the perpendicular pieces of the left player.
         for piece in 0..5 {
-            if self.get_piece_position(last_player, piece) < 12 {
-                if movable_pieces == 0 {
-                    movable_pieces = 1;
-                } else {
-                    // The game continues as long as more than 1 movable
-                    // piece remains.
-                    return false;
+            let position = self.get_piece_position(0, piece);
+            if position < Self::HOME_POSITION
+                && self.clone().fix_possible_collision(0, piece, position)
+            {
+                return false;
+            }
+        }
+
+        // Otherwise, the game would have ended after the next player's previous move.
+        self.finished_pieces(self.get_next_player()) < self.ruleset.goal
+    }
+
+    /// Return the number of squares `piece` of `player` moves from its current position, before any collision
+    ///
+    /// Return `None` if the piece is back home. A collision stops the piece earlier, just past the other piece.
+    pub fn move_length(&self, player: usize, piece: usize) -> Option<usize> {
+        let position = self.get_piece_position(player, piece);
+
+        (position < Self::HOME_POSITION)
+            .then(|| self.ruleset.regular_moves[player][piece][position])
+    }
+
+    /// Return the number of moves `piece` of `player` still needs to reach its final position, if it is never sent back
+    pub fn remaining_moves(&self, player: usize, piece: usize) -> usize {
+        let mut position = self.get_piece_position(player, piece);
+        let mut moves = 0;
+
+        while position < Self::HOME_POSITION {
+            position += self.ruleset.regular_moves[player][piece][position];
+            moves += 1;
+        }
+
+        moves
+    }
+
+    /// Return a heuristic score of the board state, positive when the top player is ahead and negative otherwise
+    ///
+    /// Each piece back home is worth 10 points, and each move needed by the opponent's pieces to finish 1 point.
+    /// Unlike the data files, this doesn't tell who wins.
+    pub fn score(&self) -> i32 {
+        let progress = |player| {
+            let remaining_moves: usize = (0..5)
+                .map(|piece| self.remaining_moves(player, piece))
+                .sum();
+            10 * self.finished_pieces(player) as i32 - remaining_moves as i32
+        };
+
+        progress(0) - progress(1)
+    }
+
+    /// Return the phase of the game, based on how far the pieces of both players have advanced
+    pub fn phase(&self) -> Phase {
+        let mut pieces_on_way_back = 0;
+        let mut pieces_home = [0; 2];
+
+        for (player, player_pieces_home) in pieces_home.iter_mut().enumerate() {
+            for piece in 0..5 {
+                let position = self.get_piece_position(player, piece);
+
+                if position >= Self::FAR_SIDE_POSITION {
+                    pieces_on_way_back += 1;
+                }
+
+                if position == Self::HOME_POSITION {
+                    *player_pieces_home += 1;
                 }
             }
         }
 
-        true
+        if pieces_home.iter().any(|&count| count >= 3) {
+            Phase::Endgame
+        } else if pieces_on_way_back < 3 {
+            Phase::Opening
+        } else {
+            Phase::Midgame
+        }
+    }
+
+    /// Return the pieces of the next player that can be moved, as labeled on the displayed board
+    ///
+    /// The list is empty when the game is over.
+    pub fn movable_labels(&self) -> Vec<usize> {
+        let mask = self.legal_moves_mask();
+
+        (0..5).filter(|&piece| mask & (1 << piece) != 0).collect()
+    }
+
+    /// Same as `movable_labels`, with typed moves
+    pub fn movable_moves(&self) -> Vec<Move> {
+        self.movable_labels().into_iter().map(Move).collect()
+    }
+
+    /// Same as `movable_labels`, but return a bitmask in which bit `piece` is set if `piece` can be moved
+    pub fn legal_moves_mask(&self) -> u8 {
+        if self.is_ended() {
+            return 0;
+        }
+
+        let next_player = self.get_next_player();
+
+        (0..5)
+            .filter(|&piece| self.get_piece_position(next_player, piece) < Self::HOME_POSITION)
+            .fold(0, |mask, piece| mask | (1 << piece))
+    }
+
+    /// Return the number of the other player's piece whose row crosses the square at `position`
+    ///
+    /// Return `None` when `position` is an initial position, the opposite side or a final position, which aren't shared.
+    pub fn perpendicular_piece(position: usize) -> Option<usize> {
+        if position.is_multiple_of(Self::FAR_SIDE_POSITION) || position > Self::HOME_POSITION {
+            None
+        } else if position < Self::FAR_SIDE_POSITION {
+            Some(position - 1)
+        } else {
+            Some(11 - position)
+        }
     }
 
     /// If two pieces are about to be on the same square, move the first one back
@@ -147,35 +625,31 @@ impl BoardState {
     /// position or the opposite side.
     /// Return `true` if such a collision occurred.
     fn fix_possible_collision(&mut self, player: usize, piece: usize, position: usize) -> bool {
-        if position.is_multiple_of(6) {
+        if position.is_multiple_of(Self::FAR_SIDE_POSITION) {
             // A collision is impossible when a piece reaches the opposite side
             // or its final position.
             return false;
         }
 
         let other_player = 1 - player;
-
-        // Get the number of the other player's piece in the perpendicular row.
-        let other_piece = if position < 6 {
-            position - 1
-        } else {
-            11 - position
-        };
+        let other_piece =
+            Self::perpendicular_piece(position).expect("Position should not be a multiple of 6");
 
         // Get position of the other player's piece.
         let other_position = self.get_piece_position(other_player, other_piece);
 
-        if other_position.is_multiple_of(6) {
+        if other_position.is_multiple_of(Self::FAR_SIDE_POSITION) {
             // A collision is impossible when the other piece is in its initial
             // or final position or on the opposite side.
             return false;
         }
         // The other player's piece hasn't reached the opposite side yet.
-        else if other_position < 6 {
+        else if other_position < Self::FAR_SIDE_POSITION {
             // Are the two pieces colliding ?
             if piece == other_position - 1 {
                 // Move the other player's piece back to its initial position.
                 self.set_piece_position(other_player, other_piece, 0);
+                log_collision(player, piece, position, other_piece, 0);
                 return true;
             }
         }
@@ -184,7 +658,14 @@ impl BoardState {
             // Are the two pieces colliding ?
             if piece == 11 - other_position {
                 // Move the other player's piece back to its opposite side.
-                self.set_piece_position(other_player, other_piece, 6);
+                self.set_piece_position(other_player, other_piece, Self::FAR_SIDE_POSITION);
+                log_collision(
+                    player,
+                    piece,
+                    position,
+                    other_piece,
+                    Self::FAR_SIDE_POSITION,
+                );
                 return true;
             }
         }
@@ -196,13 +677,94 @@ impl BoardState {
     ///
     /// Return `None` when `moved_piece` has already reached its final position or is not a valid piece.
     pub fn get_next_state(&self, moved_piece: usize) -> Option<Self> {
+        self.get_next_state_and_collision(moved_piece)
+            .map(|(new_state, _)| new_state)
+    }
+
+    /// Same as `get_next_state`, with a typed `next_move`
+    ///
+    /// Return `None` when the moved piece has already reached its final position.
+    pub fn get_next_state_for(&self, next_move: Move) -> Option<Self> {
+        self.get_next_state(next_move.into())
+    }
+
+    /// Return the move the next player plays to reach `next`, or `None` if no legal move leads from this board state to `next`
+    pub fn moved_piece_to(&self, next: &BoardState) -> Option<Move> {
+        self.movable_moves().into_iter().find(|&next_move| {
+            self.get_next_state_for(next_move)
+                .is_some_and(|s| s.get_id() == next.get_id())
+        })
+    }
+
+    /// Return the next player's pieces whose move would send an opponent's piece back
+    pub fn bumping_moves(&self) -> Vec<usize> {
+        self.movable_labels()
+            .into_iter()
+            .filter(|&piece| {
+                self.get_next_state_and_collision(piece)
+                    .is_some_and(|(_, collision)| collision)
+            })
+            .collect()
+    }
+
+    /// Return the next player's pieces which the opponent could send back if it were their turn, in increasing order
+    pub fn threatened_pieces(&self) -> Vec<usize> {
+        let player = self.get_next_player();
+        let opponent_turn = self.passed();
+        let mut threatened = [false; 5];
+
+        for opponent_piece in opponent_turn.bumping_moves() {
+            let next_state = opponent_turn
+                .get_next_state(opponent_piece)
+                .expect("Movable pieces have a next state");
+
+            for (piece, is_threatened) in threatened.iter_mut().enumerate() {
+                *is_threatened |= next_state.get_piece_position(player, piece)
+                    != self.get_piece_position(player, piece);
+            }
+        }
+
+        (0..5).filter(|&piece| threatened[piece]).collect()
+    }
+
+    /// Return an iterator over the next player's movable pieces, each with whether its move brings it back home
+    pub fn moves_reaching_home(&self) -> impl Iterator<Item = (usize, bool)> + '_ {
+        let player = self.get_next_player();
+
+        self.movable_labels().into_iter().map(move |piece| {
+            let next_state = self
+                .get_next_state(piece)
+                .expect("Movable pieces have a next state");
+
+            (
+                piece,
+                next_state.get_piece_position(player, piece) == Self::HOME_POSITION,
+            )
+        })
+    }
+
+    /// Does the move of the next player's `piece` bring it to the opposite side, where it turns around?
+    ///
+    /// Return `None` if `piece` can't be moved. Reaching home isn't a change of direction, since the piece stops there.
+    pub fn move_reverses_direction(&self, piece: usize) -> Option<bool> {
+        let player = self.get_next_player();
+        let next_state = self.get_next_state(piece)?;
+
+        Some(
+            self.get_piece_position(player, piece) < Self::FAR_SIDE_POSITION
+                && next_state.get_piece_position(player, piece) >= Self::FAR_SIDE_POSITION,
+        )
+    }
+
+    /// Same as `get_next_state`, but also return whether a collision occurred during the move
+    pub fn get_next_state_and_collision(&self, moved_piece: usize) -> Option<(Self, bool)> {
         if moved_piece > 4 {
             return None;
         }
 
         let player = self.get_next_player();
         let mut position = self.get_piece_position(player, moved_piece);
-        if position > 11 {
+        if position >= Self::HOME_POSITION {
             // The piece is in its final position and can't be moved.
             return None;
         }
@@ -210,7 +772,9 @@ impl BoardState {
         let mut new_state = self.clone();
         new_state.switch_next_player();
 
-        let mut target_position = position + REGULAR_MOVES[player][moved_piece][position];
+        let mut target_position =
+            position + self.ruleset.regular_moves[player][moved_piece][position];
+        let mut collision = false;
 
         // Move the piece, step by step.
         while position != target_position {
@@ -220,13 +784,80 @@ impl BoardState {
                 // When there is a collision, set the target position to the
                 // current piece position plus 1.
                 target_position = position + 1;
+                collision = true;
             }
         }
 
         // Save new position of the piece in `new_state`.
         new_state.set_piece_position(player, moved_piece, position);
 
-        Some(new_state)
+        Some((new_state, collision))
+    }
+
+    /// Can the next player end the game, and therefore win it, with a single move?
+    pub fn can_win_in_one(&self) -> bool {
+        !self.is_ended()
+            && self
+                .get_next_states()
+                .any(|next_state| next_state.is_ended())
+    }
+
+    /// Return the player who is sure to win by a simple race, whatever the opponent plays, or `None` if that isn't obvious
+    ///
+    /// A player wins the race when none of their unfinished pieces can meet an opponent's piece anymore, and they need
+    /// fewer moves than the opponent to bring back enough pieces. This is a quick check which doesn't need data files,
+    /// and many won board states aren't detected.
+    pub fn is_decisively_won(&self) -> Option<usize> {
+        let next_player = self.get_next_player();
+        if self.is_ended() {
+            return Some(1 - next_player);
+        }
+        if self.can_win_in_one() {
+            return Some(next_player);
+        }
+
+        let needed_moves = [0, 1].map(|player| self.needed_moves(player));
+
+        [next_player, 1 - next_player].into_iter().find(|&player| {
+            // The next player wins a tie, since they finish first.
+            let lead = needed_moves[1 - player] as isize - needed_moves[player] as isize;
+            (lead > 0 || (lead == 0 && player == next_player))
+                && (0..5).all(|piece| {
+                    self.get_piece_position(player, piece) == Self::HOME_POSITION
+                        || !self.may_meet_opponent(player, piece)
+                })
+        })
+    }
+
+    /// Return the minimum number of moves `player` needs to bring back enough pieces to win, if none is sent back
+    fn needed_moves(&self, player: usize) -> usize {
+        let mut moves: Vec<usize> = (0..5)
+            .map(|piece| self.remaining_moves(player, piece))
+            .collect();
+        moves.sort_unstable();
+
+        // Finished pieces need no move and come first.
+        moves[..self.ruleset.goal].iter().sum()
+    }
+
+    /// Can `piece` of `player` still share a square with a piece of the opponent, now or after some moves?
+    fn may_meet_opponent(&self, player: usize, piece: usize) -> bool {
+        // Return the highest row (or column) that a piece at `position` will go through, excluding the sides of the board.
+        let last_lane = |position: usize| {
+            if position <= Self::FAR_SIDE_POSITION {
+                5
+            } else {
+                Self::HOME_POSITION - position
+            }
+        };
+
+        let lane = last_lane(self.get_piece_position(player, piece));
+
+        // Pieces of both players meet on the square where each one crosses the lane of the other.
+        (0..lane).any(|other_piece| {
+            let other_position = self.get_piece_position(1 - player, other_piece);
+            other_position < Self::HOME_POSITION && piece < last_lane(other_position)
+        })
     }
 
     /// Return an iterator over the next board states, assuming the game is not over
@@ -252,17 +883,164 @@ impl BoardState {
 }
 
 impl From<u64> for BoardState {
-    /// Create a board state from its ID
+    /// Create a board state from its ID, following the official rules
     fn from(id: u64) -> Self {
-        Self { id }
+        Self::from_id_with_ruleset(id, &Ruleset::OFFICIAL)
     }
 }
 
-impl fmt::Display for BoardState {
-    /// Format the board state to display it on a terminal
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl BoardState {
+    /// Return row `row` of the board as displayed on a terminal, without line break
+    ///
+    /// Return `None` if `row` is not a row of the board. The ID displayed below the board is not part of it.
+    pub fn render_row(&self, row: usize) -> Option<String> {
+        self.render_rows(false).into_iter().nth(row)
+    }
+
+    /// Return the notation of the board state, such as `T:0,6,12,9,9 L:7,1,12,1,6 next=L` (see `from_notation`)
+    pub fn to_notation(&self) -> String {
+        let positions = |player| {
+            (0..5)
+                .map(|piece| self.get_piece_position(player, piece).to_string())
+                .collect::<Vec<String>>()
+                .join(",")
+        };
+
+        format!(
+            "T:{} L:{} next={}",
+            positions(0),
+            positions(1),
+            ["T", "L"][self.get_next_player()]
+        )
+    }
+
+    /// Return a single line describing the board state, such as `L to move | home T:0 L:0 | moves:0,1,2,3,4 | id:1`
+    ///
+    /// `moves` lists the movable pieces, or `-` if the game is over.
+    pub fn summary(&self) -> String {
+        let moves: Vec<String> = self
+            .movable_labels()
+            .iter()
+            .map(|piece| piece.to_string())
+            .collect();
+
+        format!(
+            "{} to move | home T:{} L:{} | moves:{} | id:{}",
+            ["T", "L"][self.get_next_player()],
+            self.finished_pieces(0),
+            self.finished_pieces(1),
+            if moves.is_empty() {
+                "-".to_string()
+            } else {
+                moves.join(",")
+            },
+            self.id
+        )
+    }
+
+    /// Return one progress bar per piece, such as `Top  0 [###-----]`, with one character per move of the piece
+    ///
+    /// Moves already made are shown with `#` and remaining moves with `-`.
+    pub fn render_progress(&self) -> String {
+        let mut lines = Vec::new();
+
+        for (player, name) in ["Top ", "Left"].into_iter().enumerate() {
+            for piece in 0..5 {
+                let remaining_moves = self.remaining_moves(player, piece);
+                let total_moves = BoardState::new_game_with_ruleset(player, self.ruleset)
+                    .remaining_moves(player, piece);
+
+                lines.push(format!(
+                    "{} {} [{}{}]",
+                    name,
+                    piece,
+                    "#".repeat(total_moves.saturating_sub(remaining_moves)),
+                    "-".repeat(remaining_moves)
+                ));
+            }
+        }
+
+        lines.join("\n")
+    }
+
+    /// Return the board state in plain ASCII for terminals narrower than `DISPLAY_WIDTH`
+    ///
+    /// Pieces are shown as progress bars (see `render_progress`), followed by the next player with their movable pieces and the ID.
+    pub fn render_compact(&self) -> String {
+        let next_move = if self.is_ended() {
+            format!("{} wins", ["Top", "Left"][1 - self.get_next_player()])
+        } else {
+            let movable_labels: Vec<String> = self
+                .movable_labels()
+                .iter()
+                .map(|piece| piece.to_string())
+                .collect();
+            format!(
+                "{} to move : {}",
+                ["Top", "Left"][self.get_next_player()],
+                movable_labels.join(", ")
+            )
+        };
+
+        format!(
+            "{}\n{}\n(ID : {})",
+            self.render_progress(),
+            next_move,
+            self.id
+        )
+    }
+
+    /// Return this board state and `other` as displayed on a terminal, side by side
+    pub fn render_side_by_side(&self, other: &BoardState) -> String {
+        let rows = self.render_rows(false);
+        let width = rows[0].chars().count() + 4;
+
+        let mut lines: Vec<String> = rows
+            .iter()
+            .zip(other.render_rows(false))
+            .map(|(row, other_row)| format!("{}    {}", row, other_row))
+            .collect();
+
+        // The ID of each board state is aligned with its left border.
+        let id = format!("(ID : {})", self.id);
+        lines.push(format!("{:<width$}(ID : {})", id, other.id));
+
+        lines.join("\n")
+    }
+
+    /// Return the board state as displayed on a terminal, highlighting the pieces which moved since `previous`
+    ///
+    /// The moved piece and the pieces it sent back are drawn with double arrows, e.g. `⇓` instead of `↓`.
+    pub fn render_last_move(&self, previous: &BoardState) -> String {
+        let mut highlighted = [[false; 5]; 2];
+        for (player, pieces) in highlighted.iter_mut().enumerate() {
+            for (piece, is_highlighted) in pieces.iter_mut().enumerate() {
+                *is_highlighted = self.get_piece_position(player, piece)
+                    != previous.get_piece_position(player, piece);
+            }
+        }
+
+        let mut rows = self.render_highlighted_rows(false, highlighted);
+        rows.push(format!("(ID : {})", self.id));
+        rows.join("\n")
+    }
+
+    /// Return all rows of the board as displayed on a terminal
+    ///
+    /// With `all_labels`, the numbers of all pieces are displayed, instead of those of the pieces which can be moved next.
+    fn render_rows(&self, all_labels: bool) -> Vec<String> {
+        self.render_highlighted_rows(all_labels, [[false; 5]; 2])
+    }
+
+    /// Same as `render_rows`, but draw the `highlighted` pieces, given [player][piece], with double arrows
+    fn render_highlighted_rows(
+        &self,
+        all_labels: bool,
+        highlighted: [[bool; 5]; 2],
+    ) -> Vec<String> {
         let next_player = self.get_next_player();
         let ended = self.is_ended();
+        let movable_labels = self.movable_labels();
 
         let mut board_arr = [
             [
@@ -331,18 +1109,33 @@ impl fmt::Display for BoardState {
             ],
         ];
 
+        // Add speeds of pieces on both sides of the board.
+        for piece in 0..5 {
+            let first_moves = [0, 1].map(|player| self.ruleset.first_moves[player][piece]);
+
+            board_arr[2][(piece + 1) * 4 + 4] = speed_symbol(first_moves[0]);
+            board_arr[14][(piece + 1) * 4 + 6] = speed_symbol(4 - first_moves[0]);
+            board_arr[(piece + 2) * 2][4] = speed_symbol(first_moves[1]);
+            board_arr[(piece + 2) * 2][30] = speed_symbol(4 - first_moves[1]);
+        }
+
         // Add pieces of player 0.
         for piece in 0..5 {
             let position = self.get_piece_position(0, piece);
 
-            if position < 6 {
-                board_arr[(position + 1) * 2][(piece + 1) * 4 + 5] = '↓';
+            let arrows = if highlighted[0][piece] {
+                ['⇓', '⇑']
             } else {
-                board_arr[(13 - position) * 2][(piece + 1) * 4 + 5] = '↑';
+                ['↓', '↑']
+            };
+            if position < Self::FAR_SIDE_POSITION {
+                board_arr[(position + 1) * 2][(piece + 1) * 4 + 5] = arrows[0];
+            } else {
+                board_arr[(13 - position) * 2][(piece + 1) * 4 + 5] = arrows[1];
             }
 
             // When a piece can be moved next, display its number at the top.
-            if !ended && next_player == 0 && position < 12 {
+            if all_labels || (next_player == 0 && movable_labels.contains(&piece)) {
                 board_arr[0][(piece + 1) * 4 + 5] = (piece as u8 + b'0') as char;
             }
         }
@@ -351,18 +1144,25 @@ impl fmt::Display for BoardState {
         for piece in 0..5 {
             let position = self.get_piece_position(1, piece);
 
-            if position < 6 {
-                board_arr[(piece + 2) * 2][position * 4 + 5] = '→';
+            let arrows = if highlighted[1][piece] {
+                ['⇒', '⇐']
+            } else {
+                ['→', '←']
+            };
+            if position < Self::FAR_SIDE_POSITION {
+                board_arr[(piece + 2) * 2][position * 4 + 5] = arrows[0];
             } else {
-                board_arr[(piece + 2) * 2][(12 - position) * 4 + 5] = '←';
+                board_arr[(piece + 2) * 2][(Self::HOME_POSITION - position) * 4 + 5] = arrows[1];
             }
 
             // When a piece can be moved next, display its number on the left.
-            if !ended && next_player == 1 && position < 12 {
+            if all_labels || (next_player == 1 && movable_labels.contains(&piece)) {
                 board_arr[(piece + 2) * 2][1] = (piece as u8 + b'0') as char;
             }
         }
 
+        let mut rows = Vec::with_capacity(board_arr.len());
+
         for mut line in board_arr {
             if ended || next_player == 0 {
                 // Replace light vertical lines with thick ones.
@@ -391,17 +1191,227 @@ impl fmt::Display for BoardState {
                 }
             }
 
-            writeln!(f, "{}", String::from_iter(line))?;
+            rows.push(String::from_iter(line));
+        }
+
+        rows
+    }
+}
+
+impl fmt::Display for BoardState {
+    /// Format the board state to display it on a terminal
+    ///
+    /// With the alternate flag (`{:#}`), the numbers of all pieces are displayed, e.g. for teaching.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in self.render_rows(f.alternate()) {
+            writeln!(f, "{}", row)?;
         }
 
         write!(f, "(ID : {})", self.id)
     }
 }
 
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = |player: &usize| ["top", "left"][*player];
+
+        match self {
+            Self::InvalidLayout { notation } => write!(
+                f,
+                "Invalid board notation, expected T:<positions> L:<positions> next=<T or L> : {}",
+                notation
+            ),
+            Self::WrongPieceCount { player, count } => write!(
+                f,
+                "Expected 5 positions for the {} player, found {}",
+                name(player),
+                count
+            ),
+            Self::InvalidPosition {
+                player,
+                piece,
+                position,
+            } => write!(
+                f,
+                "Invalid position of {} piece {}, expected a number from 0 to 12 : {}",
+                name(player),
+                piece,
+                position
+            ),
+            Self::UnreachablePosition {
+                player,
+                piece,
+                position,
+            } => write!(
+                f,
+                "The {} piece {} can't stop at position {}",
+                name(player),
+                piece,
+                position
+            ),
+            Self::Malformed => write!(
+                f,
+                "The board notation doesn't represent a valid board state"
+            ),
+        }
+    }
+}
+
+impl error::Error for ParseError {}
+
+/// Print a collision to stderr, only in debug builds with the `log-collisions` feature
+///
+/// `piece` of `player` reached `position` and sent `other_piece` of the other player back to position `reset_to`.
+#[allow(unused_variables)]
+fn log_collision(
+    player: usize,
+    piece: usize,
+    position: usize,
+    other_piece: usize,
+    reset_to: usize,
+) {
+    #[cfg(all(feature = "log-collisions", debug_assertions))]
+    eprintln!(
+        "collision player={} piece={} position={} other_piece={} reset_to={}",
+        player, piece, position, other_piece, reset_to
+    );
+}
+
+/// Return the symbol representing `speed` on the board
+fn speed_symbol(speed: usize) -> char {
+    match speed {
+        1 => '·',
+        2 => ':',
+        _ => '∵',
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::slice;
+
+    use crate::database::StateDatabase;
+    use crate::file_operations;
+    use crate::generate::collect_reachable_states;
+    use crate::generate::tests::generate_in;
+
     use super::*;
 
+    #[test]
+    fn official_ruleset() {
+        let regular_moves: [[[usize; 13]; 5]; 2] = [
+            [
+                [1, 1, 1, 1, 1, 1, 3, 0, 3, 3, 2, 1, 0],
+                [3, 0, 3, 3, 2, 1, 1, 1, 1, 1, 1, 1, 0],
+                [2, 0, 2, 2, 2, 1, 2, 0, 2, 2, 2, 1, 0],
+                [3, 0, 3, 3, 2, 1, 1, 1, 1, 1, 1, 1, 0],
+                [1, 1, 1, 1, 1, 1, 3, 0, 3, 3, 2, 1, 0],
+            ],
+            [
+                [3, 0, 3, 3, 2, 1, 1, 1, 1, 1, 1, 1, 0],
+                [1, 1, 1, 1, 1, 1, 3, 0, 3, 3, 2, 1, 0],
+                [2, 0, 2, 2, 2, 1, 2, 0, 2, 2, 2, 1, 0],
+                [1, 1, 1, 1, 1, 1, 3, 0, 3, 3, 2, 1, 0],
+                [3, 0, 3, 3, 2, 1, 1, 1, 1, 1, 1, 1, 0],
+            ],
+        ];
+
+        let ruleset = &Ruleset::OFFICIAL;
+        assert_eq!(ruleset.regular_moves, regular_moves);
+        assert_eq!(
+            ruleset.id_part_size,
+            [12, 12, 12, 12, 11, 11, 12, 12, 12, 12, 2]
+        );
+        assert_eq!(
+            ruleset.id_part_factor,
+            [8671297536, 722608128, 60217344, 5018112, 456192, 41472, 3456, 288, 24, 2, 1]
+        );
+    }
+
+    #[test]
+    fn id_part_factors() {
+        for ruleset in [&Ruleset::OFFICIAL, &Ruleset::IDENTICAL_FIRST_MOVES] {
+            for index in 0..11 {
+                assert_eq!(
+                    ruleset.id_part_factor[index],
+                    ruleset.id_part_size[index + 1..].iter().product::<u64>()
+                );
+            }
+
+            // All IDs must fit in a `u64`.
+            assert!(ruleset.id_part_factor[0]
+                .checked_mul(ruleset.id_part_size[0])
+                .is_some());
+
+            // All parts of the largest ID are at their maximum.
+            let b = BoardState::from_id_with_ruleset(
+                BoardState::max_valid_id_with_ruleset(ruleset),
+                ruleset,
+            );
+            for index in 0..11 {
+                assert_eq!(b.get_id_part(index), ruleset.id_part_size[index] - 1);
+            }
+        }
+    }
+
+    #[test]
+    fn identical_first_moves_ruleset() {
+        let ruleset = &Ruleset::IDENTICAL_FIRST_MOVES;
+        assert_eq!(ruleset.regular_moves[0], ruleset.regular_moves[1]);
+        assert_eq!(
+            ruleset.id_part_size,
+            [12, 12, 12, 12, 11, 11, 12, 12, 12, 12, 2]
+        );
+
+        let b = BoardState::new_game_with_ruleset(1, ruleset);
+        assert_eq!(b.get_id(), 1);
+        assert_eq!(b.get_ruleset(), ruleset);
+        assert_eq!(BoardState::new_game(1).get_ruleset(), &Ruleset::OFFICIAL);
+
+        // Left pieces move like the top pieces with the same number.
+        for (piece, position) in [1, 3, 2, 3, 1].into_iter().enumerate() {
+            let b2 = b.get_next_state(piece).unwrap();
+            assert_eq!(b2.get_piece_position(1, piece), position);
+            assert_eq!(b2.get_ruleset(), ruleset);
+        }
+
+        let board = format!("{}", b);
+        assert!(board.contains(" 0 ┃·→ │"));
+        assert!(board.contains(" 1 ┃∵→ │"));
+        assert!(board.contains("│   │  ∵┃\n"));
+    }
+
+    #[test]
+    fn goal_ruleset() {
+        static FIRST_PIECE_HOME: Ruleset = Ruleset::OFFICIAL.with_goal(1);
+        assert_eq!(Ruleset::OFFICIAL.get_goal(), Ruleset::DEFAULT_GOAL);
+        assert_eq!(FIRST_PIECE_HOME.get_goal(), 1);
+        assert_eq!(
+            FIRST_PIECE_HOME.regular_moves,
+            Ruleset::OFFICIAL.regular_moves
+        );
+
+        // The game ends as soon as a piece of the top player is back.
+        let mut b = BoardState::new_game_with_ruleset(0, &FIRST_PIECE_HOME);
+        let mut moves = 0;
+        while b.get_piece_position(0, 1) < 12 {
+            assert!(!b.is_ended());
+            b = b.get_next_state(1).unwrap();
+            b.switch_next_player();
+            moves += 1;
+        }
+        assert!(!b.is_well_formed());
+        b.switch_next_player();
+        assert!(b.is_ended());
+        assert!(b.is_well_formed());
+        assert_eq!(moves, 8);
+
+        // The same board state is not over with the official rules.
+        let b = BoardState::from(b.get_id());
+        assert!(!b.is_ended());
+        assert!(b.is_well_formed());
+    }
+
     #[test]
     fn initial_board() {
         for player in 0..=1 {
@@ -419,6 +1429,19 @@ mod tests {
         for player in 0..=1 {
             assert_eq!(BoardState::new_game(player).get_next_player(), player);
         }
+
+        for (player, number) in [(Player::Top, 0), (Player::Left, 1)] {
+            assert_eq!(usize::from(player), number);
+            assert_eq!(
+                BoardState::new_game_for(player).get_id(),
+                BoardState::new_game(number).get_id()
+            );
+            assert_eq!(
+                BoardState::new_game_for_with_ruleset(player, &Ruleset::IDENTICAL_FIRST_MOVES)
+                    .get_next_player(),
+                number
+            );
+        }
     }
 
     #[test]
@@ -448,6 +1471,33 @@ mod tests {
         assert_eq!(b.get_id(), 207360 + 120 + 14 + 10368);
     }
 
+    #[test]
+    fn from_positions() {
+        let b = BoardState::from_positions_unchecked(0, [[0; 5]; 2]);
+        assert_eq!(b.get_id(), BoardState::new_game(0).get_id());
+
+        let b = BoardState::from_positions_unchecked(0, [[0, 0, 0, 4, 5], [0, 0, 6, 0, 8]]);
+        assert_eq!(b.get_id(), 207360 + 120 + 14 + 10368);
+
+        let positions = [[12, 3, 8, 6, 0], [2, 12, 0, 11, 6]];
+        let b = BoardState::from_positions_unchecked(1, positions);
+        assert_eq!(b.get_next_player(), 1);
+
+        // Left piece 1 is home, and left piece 4 has to turn around.
+        assert_eq!(positions[1][1], BoardState::HOME_POSITION);
+        assert_eq!(positions[1][4], BoardState::FAR_SIDE_POSITION);
+        assert_eq!(b.movable_labels(), [0, 2, 3, 4]);
+        assert_eq!(
+            b.get_next_state(4).unwrap().get_piece_position(1, 4),
+            BoardState::FAR_SIDE_POSITION + 1
+        );
+        for (player, player_positions) in positions.iter().enumerate() {
+            for (piece, &position) in player_positions.iter().enumerate() {
+                assert_eq!(b.get_piece_position(player, piece), position);
+            }
+        }
+    }
+
     #[test]
     fn from() {
         for id in [0, 1, 4995120, 104055570117] {
@@ -455,6 +1505,36 @@ mod tests {
         }
     }
 
+    #[test]
+    fn arbitrary_ids() {
+        let ids = [u64::MAX, u64::MAX - 1, 1 << 63]
+            .into_iter()
+            .chain((0..10000).map(|_| fastrand::u64(..)));
+
+        // Any ID, even an invalid one, must be handled without panicking.
+        for id in ids {
+            let b = BoardState::from(id);
+            assert_eq!(b.get_id(), id);
+
+            for player in 0..=1 {
+                for piece in 0..5 {
+                    assert!(b.get_piece_position(player, piece) <= 12);
+                }
+            }
+
+            b.is_ended();
+            b.phase();
+            b.to_string();
+
+            for piece in b.movable_labels() {
+                assert!(b.get_next_state(piece).is_some());
+            }
+            b.get_next_states().for_each(|s| {
+                s.get_id();
+            });
+        }
+    }
+
     #[test]
     fn id_parts() {
         let parts: [u64; 11] = [11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1];
@@ -541,6 +1621,122 @@ mod tests {
         assert!(b.is_ended());
     }
 
+    #[test]
+    fn well_formed() {
+        let init_state = BoardState::from(85065666045);
+        for id in collect_reachable_states(slice::from_ref(&init_state)) {
+            assert!(BoardState::from(id).is_well_formed());
+        }
+        assert!(BoardState::new_game(0).is_well_formed());
+
+        let max_id = BoardState::max_valid_id();
+        assert!(!BoardState::from(max_id + 1).is_well_formed());
+        assert!(!BoardState::from(u64::MAX).is_well_formed());
+
+        // Top piece 1 and left piece 1 on the same square.
+        let mut b = BoardState::new_game(0);
+        b.set_piece_position(0, 1, 2);
+        assert!(b.is_well_formed());
+        b.set_piece_position(1, 1, 2);
+        assert!(!b.is_well_formed());
+        b.set_piece_position(1, 1, 3);
+        assert!(b.is_well_formed());
+
+        // The next player has already finished.
+        let mut b = BoardState::new_game(0);
+        for piece in 0..4 {
+            b.set_piece_position(0, piece, 12);
+        }
+        assert!(!b.is_well_formed());
+        b.set_next_player(1);
+        assert!(b.is_well_formed());
+    }
+
+    #[test]
+    fn phase() {
+        let mut b = BoardState::new_game(1);
+        assert_eq!(b.phase(), Phase::Opening);
+
+        b.set_piece_position(0, 0, 6);
+        b.set_piece_position(1, 4, 11);
+        assert_eq!(b.phase(), Phase::Opening);
+
+        b.set_piece_position(1, 2, 12);
+        assert_eq!(b.phase(), Phase::Midgame);
+
+        b.set_piece_position(0, 1, 12);
+        b.set_piece_position(0, 2, 12);
+        b.set_piece_position(1, 0, 12);
+        assert_eq!(b.phase(), Phase::Midgame);
+
+        b.set_piece_position(1, 4, 12);
+        assert_eq!(b.phase(), Phase::Endgame);
+
+        b.set_piece_position(1, 4, 0);
+        b.set_piece_position(0, 0, 12);
+        assert_eq!(b.phase(), Phase::Endgame);
+
+        assert_eq!(BoardState::from(104055570117).phase(), Phase::Endgame);
+    }
+
+    #[test]
+    fn movable_labels() {
+        let mut b = BoardState::new_game(0);
+        assert_eq!(b.movable_labels(), vec![0, 1, 2, 3, 4]);
+
+        b.set_piece_position(0, 1, 12);
+        b.set_piece_position(0, 3, 12);
+        b.set_piece_position(1, 2, 12);
+        assert_eq!(b.movable_labels(), vec![0, 2, 4]);
+
+        assert_eq!(b.legal_moves_mask(), 0b10101);
+
+        b.set_next_player(1);
+        assert_eq!(b.movable_labels(), vec![0, 1, 3, 4]);
+        assert_eq!(b.legal_moves_mask(), 0b11011);
+
+        for piece in [0, 1, 3] {
+            b.set_piece_position(1, piece, 12);
+        }
+        b.set_next_player(0);
+        assert!(b.is_ended());
+        assert!(b.movable_labels().is_empty());
+        assert_eq!(b.legal_moves_mask(), 0);
+
+        // Unlike `movable_labels`, `get_next_state` does not check whether the game is over.
+        for id in [0, 1, 85065666045, 100382226046] {
+            let b = BoardState::from(id);
+            let movable_pieces: Vec<usize> = (0..5)
+                .filter(|&piece| b.get_next_state(piece).is_some())
+                .collect();
+            assert_eq!(b.movable_labels(), movable_pieces);
+        }
+    }
+
+    #[test]
+    fn perpendicular_pieces() {
+        let pieces: Vec<Option<usize>> = (0..=13).map(BoardState::perpendicular_piece).collect();
+        assert_eq!(
+            pieces,
+            [
+                None,
+                Some(0),
+                Some(1),
+                Some(2),
+                Some(3),
+                Some(4),
+                None,
+                Some(4),
+                Some(3),
+                Some(2),
+                Some(1),
+                Some(0),
+                None,
+                None
+            ]
+        );
+    }
+
     #[test]
     fn collisions() {
         let mut b = BoardState::new_game(0);
@@ -565,6 +1761,271 @@ mod tests {
         assert_eq!(b.get_piece_position(0, 4), 6);
     }
 
+    #[test]
+    fn next_states_match_next_state() {
+        let init_states = [85065666045, 100382226046].map(BoardState::from);
+
+        for id in collect_reachable_states(&init_states) {
+            let b = BoardState::from(id);
+            let next_ids: Vec<u64> = b.get_next_states().map(|s| s.get_id()).collect();
+            let next_ids_by_piece: Vec<u64> = (0..=4)
+                .filter_map(|piece| b.get_next_state(piece))
+                .map(|s| s.get_id())
+                .collect();
+
+            assert_eq!(next_ids, next_ids_by_piece);
+        }
+    }
+
+    #[test]
+    fn bumping_moves() {
+        let mut b = BoardState::new_game(1);
+        assert!(b.bumping_moves().is_empty());
+
+        // Left piece 0 passes over the square of top piece 0.
+        b.set_piece_position(0, 0, 1);
+        assert_eq!(b.bumping_moves(), vec![0]);
+
+        // A bump is a move after which an opponent's piece is moved back.
+        for id in [0, 1, 85065666045, 100382226046, 100442443391] {
+            let b = BoardState::from(id);
+            let other_player = 1 - b.get_next_player();
+            let bumping_pieces: Vec<usize> = b
+                .movable_labels()
+                .into_iter()
+                .filter(|&piece| {
+                    let next_state = b.get_next_state(piece).unwrap();
+                    (0..5).any(|other_piece| {
+                        next_state.get_piece_position(other_player, other_piece)
+                            != b.get_piece_position(other_player, other_piece)
+                    })
+                })
+                .collect();
+            assert_eq!(b.bumping_moves(), bumping_pieces);
+        }
+    }
+
+    #[test]
+    fn scores() {
+        // Both players need as many moves at the start of the game.
+        for player in 0..=1 {
+            assert_eq!(BoardState::new_game(player).score(), 0);
+        }
+
+        // Moving a piece makes its player's score better.
+        let b = BoardState::new_game(0);
+        for piece in b.movable_labels() {
+            let moved = b.get_next_state(piece).unwrap();
+            assert_eq!(moved.score(), 1);
+            for other_piece in moved.movable_labels() {
+                assert!(moved.get_next_state(other_piece).unwrap().score() <= 1);
+            }
+        }
+
+        let b = BoardState::from_positions_unchecked(0, [[12, 12, 0, 0, 0], [12, 0, 0, 0, 0]]);
+        // One more piece home for top, whose other pieces need 22 moves against 30.
+        assert_eq!(b.score(), 10 + 30 - 22);
+        let b = BoardState::from_positions_unchecked(1, [[12, 12, 12, 12, 0], [0, 6, 6, 6, 0]]);
+        assert!(b.score() > 0);
+        let b = BoardState::from_positions_unchecked(1, [[0; 5], [12, 12, 12, 12, 0]]);
+        assert!(b.score() < 0);
+    }
+
+    #[test]
+    fn typed_moves() {
+        for piece in 0..5 {
+            let next_move = Move::new(piece).unwrap();
+            assert_eq!(next_move.piece(), piece);
+            assert_eq!(usize::from(next_move), piece);
+        }
+        assert_eq!(Move::new(5), None);
+        assert_eq!(Move::new(usize::MAX), None);
+        assert_eq!(Move::new(3).unwrap().to_string(), "3");
+
+        for id in [0, 1, 85065666045, 100382226046] {
+            let b = BoardState::from(id);
+            for piece in 0..5 {
+                assert_eq!(
+                    b.get_next_state_for(Move::new(piece).unwrap())
+                        .map(|s| s.get_id()),
+                    b.get_next_state(piece).map(|s| s.get_id())
+                );
+            }
+            assert_eq!(
+                b.movable_moves(),
+                b.movable_labels()
+                    .into_iter()
+                    .map(|piece| Move::new(piece).unwrap())
+                    .collect::<Vec<Move>>()
+            );
+        }
+    }
+
+    #[test]
+    fn equality() {
+        let b = BoardState::new_game(0).get_next_state(4).unwrap();
+        let same = BoardState::from_notation(&b.to_notation()).unwrap();
+        assert!(b == same);
+        assert!(b == BoardState::from(b.get_id()));
+        assert!(b != b.passed());
+        assert!(
+            BoardState::new_game(1)
+                != BoardState::new_game_with_ruleset(1, &Ruleset::IDENTICAL_FIRST_MOVES)
+        );
+
+        let states: std::collections::HashSet<BoardState> =
+            [b.clone(), same, b.passed()].into_iter().collect();
+        assert_eq!(states.len(), 2);
+        assert!(states.contains(&BoardState::from(b.get_id())));
+
+        // Equal rules stored at different addresses give equal board states, with the same hash.
+        let ruleset: &'static Ruleset = Box::leak(Box::new(Ruleset::OFFICIAL.clone()));
+        let copy = BoardState::from_id_with_ruleset(b.get_id(), ruleset);
+        assert!(!ptr::eq(copy.get_ruleset(), b.get_ruleset()));
+        assert!(copy == b);
+        let hash = |state: &BoardState| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            state.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&copy), hash(&b));
+        assert!(states.contains(&copy));
+    }
+
+    #[test]
+    fn moved_pieces() {
+        let b = BoardState::new_game(0);
+        for piece in 0..5 {
+            let next = b.get_next_state(piece).unwrap();
+            assert_eq!(b.moved_piece_to(&next), Move::new(piece));
+            assert_eq!(next.moved_piece_to(&b), None);
+        }
+        assert_eq!(b.moved_piece_to(&b), None);
+        assert_eq!(b.moved_piece_to(&b.passed()), None);
+
+        // Two moves apart.
+        let next = b.get_next_state(4).unwrap().get_next_state(0).unwrap();
+        assert_eq!(b.moved_piece_to(&next), None);
+
+        // No move follows the end of the game.
+        let b = BoardState::from_positions_unchecked(0, [[0; 5], [12, 12, 12, 12, 0]]);
+        assert!(b.is_ended());
+        let next = b.get_next_state(0).unwrap();
+        assert_eq!(b.moved_piece_to(&next), None);
+    }
+
+    #[test]
+    fn threatened_pieces() {
+        let mut b = BoardState::new_game(0);
+        assert!(b.threatened_pieces().is_empty());
+
+        // Left piece 0 would pass over the square of top piece 0.
+        b.set_piece_position(0, 0, 1);
+        assert_eq!(b.threatened_pieces(), vec![0]);
+
+        // The pieces of the opponent are not considered.
+        b.switch_next_player();
+        assert!(b.threatened_pieces().is_empty());
+
+        // A threatened piece is sent back by at least one move of the opponent.
+        for id in [0, 1, 85065666045, 100382226046, 100442443391] {
+            let b = BoardState::from(id);
+            let player = b.get_next_player();
+            let mut opponent_turn = b.clone();
+            opponent_turn.switch_next_player();
+
+            let threatened: Vec<usize> = (0..5)
+                .filter(|&piece| {
+                    opponent_turn.get_next_states().any(|next_state| {
+                        next_state.get_piece_position(player, piece)
+                            != b.get_piece_position(player, piece)
+                    })
+                })
+                .collect();
+            assert_eq!(b.threatened_pieces(), threatened);
+        }
+    }
+
+    #[test]
+    fn move_lengths() {
+        let b = BoardState::new_game(0);
+        for player in 0..=1 {
+            for piece in 0..5 {
+                assert_eq!(
+                    b.move_length(player, piece),
+                    Some(b.ruleset.first_moves[player][piece])
+                );
+            }
+        }
+
+        // Pieces stop on the opposite side, and top piece 0 moves 3 squares on its way back, but only 1 is left.
+        let b = BoardState::from_positions_unchecked(0, [[11, 5, 6, 12, 0], [0; 5]]);
+        assert_eq!(b.move_length(0, 0), Some(1));
+        assert_eq!(b.move_length(0, 1), Some(1));
+        assert_eq!(b.move_length(0, 2), Some(2));
+        assert_eq!(b.move_length(0, 3), None);
+
+        // Without collision, pieces move by their move length.
+        let next_state = b.get_next_state(2).unwrap();
+        assert_eq!(next_state.get_piece_position(0, 2), 8);
+    }
+
+    #[test]
+    fn direction_reversals() {
+        let b = BoardState::new_game(0);
+        assert!((0..5).all(|piece| b.move_reverses_direction(piece) == Some(false)));
+        assert_eq!(b.move_reverses_direction(5), None);
+
+        // Top pieces 1 and 3 reach the opposite side, top piece 0 is already on its way back and top piece 2 is home.
+        let b = BoardState::from_positions_unchecked(0, [[8, 3, 12, 5, 4], [0; 5]]);
+        assert_eq!(b.move_reverses_direction(0), Some(false));
+        assert_eq!(b.move_reverses_direction(1), Some(true));
+        assert_eq!(b.move_reverses_direction(2), None);
+        assert_eq!(b.move_reverses_direction(3), Some(true));
+        assert_eq!(b.move_reverses_direction(4), Some(false));
+
+        // A piece on the opposite side has already turned around.
+        let b = BoardState::from_positions_unchecked(1, [[0; 5], [6, 0, 0, 0, 0]]);
+        assert_eq!(b.move_reverses_direction(0), Some(false));
+    }
+
+    #[test]
+    fn finishing_moves() {
+        let b = BoardState::new_game(0);
+        assert!(b
+            .moves_reaching_home()
+            .all(|(_, reaches_home)| !reaches_home));
+        assert_eq!(
+            b.moves_reaching_home()
+                .map(|(piece, _)| piece)
+                .collect::<Vec<usize>>(),
+            b.movable_labels()
+        );
+
+        // Top piece 0 is one square from home, and top piece 2 is home already.
+        let b = BoardState::from_positions_unchecked(0, [[11, 0, 12, 8, 0], [0; 5]]);
+        assert_eq!(
+            b.moves_reaching_home().collect::<Vec<(usize, bool)>>(),
+            [(0, true), (1, false), (3, false), (4, false)]
+        );
+
+        // Moves reaching home are those after which the mover has one more piece home.
+        for id in [85065666045, 100382226046, 100442443391] {
+            let b = BoardState::from(id);
+            let player = b.get_next_player();
+            for (piece, reaches_home) in b.moves_reaching_home() {
+                let next_state = b.get_next_state(piece).unwrap();
+                assert_eq!(
+                    reaches_home,
+                    next_state.finished_pieces(player) > b.finished_pieces(player)
+                );
+            }
+        }
+
+        let ended = BoardState::from_positions_unchecked(0, [[0; 5], [12, 12, 12, 12, 0]]);
+        assert_eq!(ended.moves_reaching_home().count(), 0);
+    }
+
     #[test]
     fn next_state() {
         let mut b = BoardState::new_game(1);
@@ -683,6 +2144,358 @@ mod tests {
         }
     }
 
+    #[test]
+    fn chained_collisions() {
+        // Top piece 2 jumps over left pieces 3 and 4, and lands on the opposite side.
+        let mut b = BoardState::new_game(0);
+        b.set_piece_position(0, 2, 3);
+        b.set_piece_position(1, 3, 3);
+        b.set_piece_position(1, 4, 3);
+
+        let (b2, collision) = b.get_next_state_and_collision(2).unwrap();
+        assert!(collision);
+        assert_eq!(b2.get_piece_position(0, 2), 6);
+        assert_eq!(b2.get_piece_position(1, 3), 0);
+        assert_eq!(b2.get_piece_position(1, 4), 0);
+
+        // Top piece 0 jumps over left pieces 2, 1 and 0 on its way back, and reaches its final position.
+        let mut b = BoardState::new_game(0);
+        b.set_piece_position(0, 0, 8);
+        for piece in 0..=2 {
+            b.set_piece_position(1, piece, 11);
+        }
+
+        let (b2, collision) = b.get_next_state_and_collision(0).unwrap();
+        assert!(collision);
+        assert_eq!(b2.get_piece_position(0, 0), 12);
+        for piece in 0..=2 {
+            assert_eq!(b2.get_piece_position(1, piece), 6);
+        }
+
+        // A gap between two pieces stops the chain : only the first piece is jumped over.
+        b.set_piece_position(1, 1, 0);
+        let b2 = b.get_next_state(0).unwrap();
+        assert_eq!(b2.get_piece_position(0, 0), 10);
+        assert_eq!(b2.get_piece_position(1, 2), 6);
+        assert_eq!(b2.get_piece_position(1, 0), 11);
+    }
+
+    #[test]
+    fn single_rows() {
+        for id in [0, 1, 85065666045, 100382226046] {
+            let b = BoardState::from(id);
+            let board = b.to_string();
+            let lines: Vec<&str> = board.lines().collect();
+
+            // All lines but the ID are rows of the board.
+            assert_eq!(lines.len(), 17);
+            for (row, line) in lines[..16].iter().enumerate() {
+                assert_eq!(b.render_row(row).unwrap(), *line);
+            }
+            assert_eq!(b.render_row(16), None);
+        }
+
+        assert_eq!(
+            BoardState::from(1).render_row(4).unwrap(),
+            " 0 ┃∵→ │   │   │   │   │   │  ·┃"
+        );
+    }
+
+    #[test]
+    fn decisive_races() {
+        assert_eq!(BoardState::new_game(0).is_decisively_won(), None);
+
+        // Left needs 2 moves and its pieces won't meet top piece 3, which needs 5 moves.
+        let b = BoardState::from_positions_unchecked(0, [[12, 12, 12, 7, 0], [12, 12, 10, 9, 11]]);
+        assert!(!b.can_win_in_one());
+        assert_eq!(b.is_decisively_won(), Some(1));
+
+        // Left piece 2 can still be sent back by top piece 3.
+        let b = BoardState::from_positions_unchecked(0, [[12, 12, 12, 7, 0], [12, 12, 6, 9, 11]]);
+        assert_eq!(b.is_decisively_won(), None);
+
+        let init_state = BoardState::from(85065666045);
+        file_operations::tests::with_tempdir(|dir| {
+            generate_in(dir, slice::from_ref(&init_state));
+            let database = StateDatabase::new(dir);
+
+            let mut detected_races = 0;
+            for id in collect_reachable_states(slice::from_ref(&init_state)) {
+                let state = BoardState::from(id);
+                if let Some(winner) = state.is_decisively_won() {
+                    assert_eq!(database.evaluate(id).unwrap(), Some(winner));
+                    if !state.is_ended() && !state.can_win_in_one() {
+                        detected_races += 1;
+                    }
+                }
+            }
+            assert!(detected_races > 0);
+        });
+    }
+
+    #[test]
+    fn one_line_summary() {
+        assert_eq!(
+            BoardState::new_game(1).summary(),
+            "L to move | home T:0 L:0 | moves:0,1,2,3,4 | id:1"
+        );
+
+        let b = BoardState::from_positions_unchecked(0, [[12, 12, 0, 6, 12], [12, 3, 0, 0, 9]]);
+        assert_eq!(
+            b.summary(),
+            format!("T to move | home T:3 L:1 | moves:2,3 | id:{}", b.get_id())
+        );
+
+        let b = BoardState::from_positions_unchecked(0, [[0; 5], [12, 12, 12, 12, 0]]);
+        assert!(b.is_ended());
+        assert!(b.summary().contains("| moves:- |"));
+    }
+
+    #[test]
+    fn progress() {
+        let b = BoardState::new_game(0);
+        for player in 0..=1 {
+            for (piece, total) in [8, 8, 6, 8, 8].into_iter().enumerate() {
+                assert_eq!(b.remaining_moves(player, piece), total);
+            }
+        }
+        assert_eq!(
+            b.render_progress().lines().next().unwrap(),
+            "Top  0 [--------]"
+        );
+
+        // Top piece 1 moves 3 squares at a time on its way out, then 1 square at a time on its way back.
+        let b = b.get_next_state(1).unwrap();
+        assert_eq!(b.remaining_moves(0, 1), 7);
+        assert_eq!(
+            b.render_progress().lines().nth(1).unwrap(),
+            "Top  1 [#-------]"
+        );
+
+        let mut b = BoardState::new_game(1);
+        b.set_piece_position(1, 4, 12);
+        assert_eq!(b.remaining_moves(1, 4), 0);
+        assert_eq!(
+            b.render_progress().lines().last().unwrap(),
+            "Left 4 [########]"
+        );
+    }
+
+    #[test]
+    fn notation_parsing() {
+        let b = BoardState::from_notation("T:0,6,12,9,9 L:7,1,12,1,6 next=L").unwrap();
+        let expected =
+            BoardState::from_positions_unchecked(1, [[0, 6, 12, 9, 9], [7, 1, 12, 1, 6]]);
+        assert_eq!(b.get_id(), expected.get_id());
+        assert_eq!(
+            BoardState::from_notation("T:0,0,0,0,0 L:0,0,0,0,0 next=T")
+                .unwrap()
+                .get_id(),
+            BoardState::new_game(0).get_id()
+        );
+
+        let b = BoardState::from_notation_with_ruleset(
+            "T:0,0,0,0,0 L:0,0,0,0,0 next=L",
+            &Ruleset::IDENTICAL_FIRST_MOVES,
+        )
+        .unwrap();
+        assert_eq!(b.get_ruleset(), &Ruleset::IDENTICAL_FIRST_MOVES);
+        assert_eq!(b.get_next_player(), 1);
+
+        for notation in [
+            "",
+            "T:0,0,0,0,0 L:0,0,0,0,0",
+            "T:0,0,0,0,0 L:0,0,0,0,0 next=X",
+            "L:0,0,0,0,0 T:0,0,0,0,0 next=T",
+            "T:0,0,0,0,0 L:0,0,0,0,0 next=T extra",
+        ] {
+            assert_eq!(
+                BoardState::from_notation(notation).err().unwrap(),
+                ParseError::InvalidLayout {
+                    notation: notation.to_string()
+                }
+            );
+        }
+
+        assert_eq!(
+            BoardState::from_notation("T:0,0,0,0 L:0,0,0,0,0 next=T")
+                .err()
+                .unwrap(),
+            ParseError::WrongPieceCount {
+                player: 0,
+                count: 4
+            }
+        );
+        assert_eq!(
+            BoardState::from_notation("T:0,0,0,0,0 L:0,0,0,0,0,0 next=T")
+                .err()
+                .unwrap(),
+            ParseError::WrongPieceCount {
+                player: 1,
+                count: 6
+            }
+        );
+
+        for position in ["13", "-1", "x", ""] {
+            let notation = format!("T:0,0,0,0,0 L:0,0,{},0,0 next=T", position);
+            assert_eq!(
+                BoardState::from_notation(&notation).err().unwrap(),
+                ParseError::InvalidPosition {
+                    player: 1,
+                    piece: 2,
+                    position: position.to_string()
+                }
+            );
+        }
+
+        // Top piece 1 moves 3 squares at a time on its way out.
+        let error = BoardState::from_notation("T:0,1,0,0,0 L:0,0,0,0,0 next=T")
+            .err()
+            .unwrap();
+        assert_eq!(
+            error,
+            ParseError::UnreachablePosition {
+                player: 0,
+                piece: 1,
+                position: 1
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "The top piece 1 can't stop at position 1"
+        );
+
+        // Top piece 0 at position 2 and left piece 1 at position 1 share a square.
+        assert_eq!(
+            BoardState::from_notation("T:2,0,0,0,0 L:0,1,0,0,0 next=T")
+                .err()
+                .unwrap(),
+            ParseError::Malformed
+        );
+    }
+
+    #[test]
+    fn notation_round_trip() {
+        assert_eq!(
+            BoardState::new_game(1).to_notation(),
+            "T:0,0,0,0,0 L:0,0,0,0,0 next=L"
+        );
+
+        let notation = "T:0,6,12,9,9 L:7,1,12,1,6 next=L";
+        assert_eq!(
+            BoardState::from_notation(notation).unwrap().to_notation(),
+            notation
+        );
+
+        for id in [0, 1, 85065666045, 104055570117] {
+            let b = BoardState::from(id);
+            let notation = b.to_notation();
+            assert_eq!(BoardState::from_notation(&notation).unwrap().get_id(), id);
+            assert_eq!(
+                notation.contains("12"),
+                b.finished_pieces(0) + b.finished_pieces(1) > 0
+            );
+        }
+    }
+
+    #[test]
+    fn compact_render() {
+        let b = BoardState::new_game(1);
+        assert_eq!(
+            b.render_row(0).unwrap().chars().count(),
+            BoardState::DISPLAY_WIDTH
+        );
+
+        let rendered = b.render_compact();
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(
+            lines[..10],
+            b.render_progress().lines().collect::<Vec<&str>>()
+        );
+        assert_eq!(lines[10..], ["Left to move : 0, 1, 2, 3, 4", "(ID : 1)"]);
+        assert!(rendered.is_ascii());
+        assert!(lines
+            .iter()
+            .all(|line| line.len() < BoardState::DISPLAY_WIDTH));
+
+        let b = BoardState::from_positions_unchecked(0, [[12, 12, 0, 6, 12], [12, 3, 0, 0, 9]]);
+        assert_eq!(
+            b.render_compact().lines().nth(10),
+            Some("Top to move : 2, 3")
+        );
+
+        let b = BoardState::from_positions_unchecked(0, [[0; 5], [12, 12, 12, 12, 0]]);
+        assert_eq!(b.render_compact().lines().nth(10), Some("Left wins"));
+    }
+
+    #[test]
+    fn side_by_side() {
+        let (a, b) = (BoardState::from(1), BoardState::from(85065666045));
+        let rendered = a.render_side_by_side(&b);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines.len(), 17);
+
+        for (row, line) in lines[..16].iter().enumerate() {
+            assert_eq!(
+                *line,
+                format!(
+                    "{}    {}",
+                    a.render_row(row).unwrap(),
+                    b.render_row(row).unwrap()
+                )
+            );
+        }
+        assert_eq!(
+            lines[16].split_whitespace().collect::<Vec<&str>>(),
+            ["(ID", ":", "1)", "(ID", ":", "85065666045)"]
+        );
+    }
+
+    #[test]
+    fn last_move_highlight() {
+        let plain = |rendered: &str| {
+            rendered
+                .chars()
+                .map(|c| match c {
+                    '⇓' => '↓',
+                    '⇑' => '↑',
+                    '⇒' => '→',
+                    '⇐' => '←',
+                    _ => c,
+                })
+                .collect::<String>()
+        };
+        let highlighted_count = |rendered: &str| rendered.matches(['⇓', '⇑', '⇒', '⇐']).count();
+
+        let mut rng = fastrand::Rng::with_seed(3);
+        let mut state = BoardState::new_game(0);
+        let mut collisions = 0;
+        while !state.is_ended() {
+            let labels = state.movable_labels();
+            let (next_state, collision) = state
+                .get_next_state_and_collision(labels[rng.usize(..labels.len())])
+                .unwrap();
+
+            let rendered = next_state.render_last_move(&state);
+            assert_eq!(plain(&rendered), next_state.to_string());
+            if collision {
+                assert!(highlighted_count(&rendered) >= 2);
+                collisions += 1;
+            } else {
+                assert_eq!(highlighted_count(&rendered), 1);
+            }
+
+            // Only the moved piece of the next player is highlighted.
+            let arrows = [['⇓', '⇑'], ['⇒', '⇐']][state.get_next_player()];
+            assert_eq!(rendered.matches(arrows).count(), 1);
+
+            state = next_state;
+        }
+        assert!(collisions > 0);
+
+        assert_eq!(state.render_last_move(&state), state.to_string());
+    }
+
     #[test]
     fn display() {
         assert_eq!(
@@ -747,5 +2560,42 @@ mod tests {
        ┗━━━┻━━━┻━━━┻━━━┻━━━┛    
 (ID : 104055570117)"
         );
+
+        // Pieces back home are shown in their initial square, pointing back, and nowhere else.
+        let b = BoardState::from_positions_unchecked(0, [[12, 12, 0, 6, 12], [12, 0, 12, 11, 6]]);
+        assert_eq!(
+            format!("{}", b),
+            "                 2   3          
+       ┏━━━┳━━━┳━━━┳━━━┳━━━┓    
+     ■ ┃·↑ ┃∵↑ ┃:↓ ┃∵  ┃·↑ ┃ ■  
+   ┏━━━╉───╂───╂───╂───╂───╊━━━┓
+   ┃∵← ┃   ┃   ┃   ┃   ┃   ┃  ·┃
+   ┣━━━╉───╂───╂───╂───╂───╊━━━┫
+   ┃·→ ┃   ┃   ┃   ┃   ┃   ┃  ∵┃
+   ┣━━━╉───╂───╂───╂───╂───╊━━━┫
+   ┃:← ┃   ┃   ┃   ┃   ┃   ┃  :┃
+   ┣━━━╉───╂───╂───╂───╂───╊━━━┫
+   ┃·  ┃ ← ┃   ┃   ┃   ┃   ┃  ∵┃
+   ┣━━━╉───╂───╂───╂───╂───╊━━━┫
+   ┃∵  ┃   ┃   ┃   ┃   ┃   ┃ ←·┃
+   ┗━━━╉───╂───╂───╂───╂───╊━━━┛
+     ■ ┃  ∵┃  ·┃  :┃ ↑·┃  ∵┃ ■  
+       ┗━━━┻━━━┻━━━┻━━━┻━━━┛    
+(ID : 103995788242)"
+        );
+        let arrows = |c| format!("{}", b).matches(c).count();
+        assert_eq!([arrows('↓'), arrows('↑')], [1, 4]);
+        assert_eq!([arrows('→'), arrows('←')], [1, 4]);
+
+        // The alternate flag labels all pieces, even after the end of the game.
+        let board = format!("{:#}", BoardState::from(104055570117));
+        assert!(board.starts_with("         0   1   2   3   4      \n"));
+        for piece in 0..5 {
+            assert!(board.contains(&format!("\n {} ┃", piece)));
+        }
+        assert_eq!(
+            board.replace(char::is_numeric, " "),
+            format!("{}", BoardState::from(104055570117)).replace(char::is_numeric, " ")
+        );
     }
 }
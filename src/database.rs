@@ -0,0 +1,494 @@
+use std::cmp::Ordering;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::{error, fmt};
+
+use crate::board_state::{BoardState, Ruleset};
+use crate::file_operations::{self, DataError, StateReader};
+
+/// Evaluation of a board state for one of the players
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BoardStateEval {
+    Win,
+    Draw, // Endless game.
+    Loss,
+    Unknown, // Not covered by the data files.
+}
+
+/// Win > Draw > Loss, while an unknown evaluation is only equal to itself and can't be compared to any other one
+impl PartialOrd for BoardStateEval {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        let rank = |eval: &Self| match eval {
+            Self::Loss => Some(0),
+            Self::Draw => Some(1),
+            Self::Win => Some(2),
+            Self::Unknown => None,
+        };
+
+        match (rank(self), rank(other)) {
+            (Some(rank), Some(other_rank)) => Some(rank.cmp(&other_rank)),
+            // Consistent with `==`, as required by `PartialOrd`.
+            (None, None) => Some(Ordering::Equal),
+            _ => None,
+        }
+    }
+}
+
+/// Data file of a database, opened on its first lookup and kept open for the next ones
+struct DataFile {
+    path: String,
+    reader: OnceLock<Mutex<StateReader>>,
+}
+
+impl DataFile {
+    fn new(path: String) -> Self {
+        DataFile {
+            path,
+            reader: OnceLock::new(),
+        }
+    }
+
+    /// Return whether the data file contains the board state `id`, opening it if needed
+    ///
+    /// A file which can't be opened is opened again on the next lookup.
+    fn contains(&self, id: u64) -> Result<bool, DataError> {
+        self.with_reader(|reader| reader.contains(id))
+    }
+
+    /// Same as `contains`, but for each board state of `ids` (see `StateReader::contains_all`)
+    fn contains_all(&self, ids: &[u64]) -> Result<Vec<bool>, DataError> {
+        self.with_reader(|reader| reader.contains_all(ids))
+    }
+
+    /// Same as `contains`, but return a board state ID selected at random (see `StateReader::random_state`)
+    fn random_state(&self, rng: &mut fastrand::Rng) -> Result<Option<u64>, DataError> {
+        self.with_reader(|reader| reader.random_state(rng))
+    }
+
+    /// Call `f` with the reader of the data file, opening it if needed
+    fn with_reader<T>(
+        &self,
+        f: impl FnOnce(&mut StateReader) -> Result<T, DataError>,
+    ) -> Result<T, DataError> {
+        if self.reader.get().is_none() {
+            let reader = StateReader::open(&self.path)?;
+            // If another thread opened the file in the meantime, this reader is dropped.
+            let _ = self.reader.set(Mutex::new(reader));
+        }
+
+        f(&mut self
+            .reader
+            .get()
+            .expect("The data file should be open")
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+/// Data files generated in a single directory
+///
+/// Each file is opened on its first lookup and kept open, so a single database can serve many games.
+pub struct StateDatabase {
+    all_states_file: DataFile,
+    winning_states_files: [DataFile; 2],
+}
+
+impl StateDatabase {
+    /// Return the database made of the data files stored in `directory`
+    ///
+    /// The files are only opened when they are queried.
+    pub fn new(directory: impl AsRef<Path>) -> Self {
+        Self::with_prefix(directory, "")
+    }
+
+    /// Return the database made of the data files stored in `directory`, whose names start with `prefix` (see `generate_with_prefix`)
+    pub fn with_prefix(directory: impl AsRef<Path>, prefix: &str) -> Self {
+        let get_path = |file_name| file_operations::data_file_path(&directory, prefix, file_name);
+
+        StateDatabase {
+            all_states_file: DataFile::new(get_path(file_operations::ALL_STATES_PATH)),
+            winning_states_files: file_operations::WINNING_STATES_PATH
+                .map(|path| DataFile::new(get_path(path))),
+        }
+    }
+
+    /// Return the database made of the data files stored in the current directory
+    pub fn in_current_dir() -> Self {
+        Self::new("")
+    }
+
+    /// Return the path to the data file with all states of the database
+    pub fn all_states_path(&self) -> &str {
+        &self.all_states_file.path
+    }
+
+    /// Return the path to the data file with the winning states of `player`
+    pub fn winning_states_path(&self, player: usize) -> &str {
+        &self.winning_states_files[player].path
+    }
+
+    /// Return whether the board state `id` was reached during generation
+    pub fn contains(&self, id: u64) -> Result<bool, DataError> {
+        self.all_states_file.contains(id)
+    }
+
+    /// Return the player who wins from the board state `id`, or `None` if the game is a draw
+    ///
+    /// The result is meaningless if the database does not contain `id`.
+    pub fn evaluate(&self, id: u64) -> Result<Option<usize>, DataError> {
+        for player in 0..=1 {
+            if self.is_winning(id, player)? {
+                return Ok(Some(player));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Return the outcome of the board state `id` for `player`, who may or may not be the next player
+    ///
+    /// Winning states are looked up first, so telling a draw from an unknown state takes three lookups.
+    pub fn verdict(&self, id: u64, player: usize) -> Result<BoardStateEval, DataError> {
+        if self.is_winning(id, player)? {
+            Ok(BoardStateEval::Win)
+        } else if self.is_winning(id, 1 - player)? {
+            Ok(BoardStateEval::Loss)
+        } else if self.contains(id)? {
+            Ok(BoardStateEval::Draw)
+        } else {
+            Ok(BoardStateEval::Unknown)
+        }
+    }
+
+    /// Same as `verdict`, but for each board state of `ids`, reading each chunk of the data files at most once
+    pub fn verdicts(&self, ids: &[u64], player: usize) -> Result<Vec<BoardStateEval>, DataError> {
+        let winning = self.winning_states_files[player].contains_all(ids)?;
+        let losing = self.winning_states_files[1 - player].contains_all(ids)?;
+        let contained = self.all_states_file.contains_all(ids)?;
+
+        Ok((0..ids.len())
+            .map(|index| {
+                if winning[index] {
+                    BoardStateEval::Win
+                } else if losing[index] {
+                    BoardStateEval::Loss
+                } else if contained[index] {
+                    BoardStateEval::Draw
+                } else {
+                    BoardStateEval::Unknown
+                }
+            })
+            .collect())
+    }
+
+    /// Return whether `player` wins from the board state `id`
+    pub fn is_winning(&self, id: u64, player: usize) -> Result<bool, DataError> {
+        self.winning_states_files[player].contains(id)
+    }
+
+    /// Return a board state following `ruleset`, selected at random among all states of the database
+    ///
+    /// Only the chunk of the selected state is read (see `StateReader::random_state`). Return `None` if the database is empty.
+    pub fn random_state(
+        &self,
+        ruleset: &'static Ruleset,
+        rng: &mut fastrand::Rng,
+    ) -> Result<Option<BoardState>, DataError> {
+        Ok(self
+            .all_states_file
+            .random_state(rng)?
+            .map(|id| BoardState::from_id_with_ruleset(id, ruleset)))
+    }
+}
+
+/// Error that occurred while querying a `DatabaseSet`
+#[derive(Debug)]
+pub enum DatabaseSetError {
+    /// A data file could not be read
+    DataError(DataError),
+
+    /// No database contains the board state `id`
+    UnknownState { id: u64 },
+
+    /// Databases containing the board state `id` disagree on its evaluation
+    Conflict { id: u64 },
+}
+
+impl From<DataError> for DatabaseSetError {
+    fn from(error: DataError) -> Self {
+        Self::DataError(error)
+    }
+}
+
+impl fmt::Display for DatabaseSetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DataError(error) => error.fmt(f),
+            Self::UnknownState { id } => {
+                write!(f, "No database contains board state ID : {}", id)
+            }
+            Self::Conflict { id } => {
+                write!(f, "Databases disagree on board state ID : {}", id)
+            }
+        }
+    }
+}
+
+impl error::Error for DatabaseSetError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::DataError(error) => Some(error),
+            Self::UnknownState { .. } | Self::Conflict { .. } => None,
+        }
+    }
+}
+
+/// Several databases queried as a single one, e.g. databases generated from different subtrees
+pub struct DatabaseSet {
+    databases: Vec<StateDatabase>,
+}
+
+impl DatabaseSet {
+    /// Return the set made of `databases`
+    pub fn new(databases: Vec<StateDatabase>) -> Self {
+        DatabaseSet { databases }
+    }
+
+    /// Return whether any database contains the board state `id`
+    pub fn contains(&self, id: u64) -> Result<bool, DatabaseSetError> {
+        for database in &self.databases {
+            if database.contains(id)? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Return the player who wins from the board state `id`, or `None` if the game is a draw
+    ///
+    /// Every database containing `id` is checked, to make sure that they all agree.
+    pub fn evaluate(&self, id: u64) -> Result<Option<usize>, DatabaseSetError> {
+        let mut result = None;
+
+        for database in &self.databases {
+            if !database.contains(id)? {
+                continue;
+            }
+
+            let winner_opt = database.evaluate(id)?;
+            match result {
+                Some(previous_winner_opt) if previous_winner_opt != winner_opt => {
+                    return Err(DatabaseSetError::Conflict { id });
+                }
+                _ => result = Some(winner_opt),
+            }
+        }
+
+        result.ok_or(DatabaseSetError::UnknownState { id })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::{fs, slice};
+
+    use crate::board_state::BoardState;
+    use crate::file_operations::DataFormat;
+    use crate::generate::tests::generate_in;
+    use crate::generate::{generate, generate_with_prefix, Verbosity};
+
+    use super::*;
+
+    #[test]
+    fn eval_ordering() {
+        use BoardStateEval::*;
+
+        assert!(Win > Draw && Draw > Loss && Win > Loss);
+        assert!(Draw >= Draw);
+        assert_eq!(Unknown.partial_cmp(&Unknown), Some(Ordering::Equal));
+        for eval in [Win, Draw, Loss] {
+            assert_eq!(eval.partial_cmp(&Unknown), None);
+            assert_eq!(Unknown.partial_cmp(&eval), None);
+        }
+    }
+
+    #[test]
+    fn database_set() {
+        let init_states = [5057791486, 85065666045].map(BoardState::from);
+
+        file_operations::tests::with_tempdir(|dir| {
+            let generate_in_dir = |name, init_state: &BoardState| {
+                fs::create_dir(dir.join(name)).unwrap();
+                generate_in(&dir.join(name), slice::from_ref(init_state));
+            };
+            generate_in_dir("draw", &init_states[0]);
+            generate_in_dir("win", &init_states[1]);
+
+            let draw = StateDatabase::new(dir.join("draw"));
+            assert!(draw.contains(5057791486).unwrap());
+            assert!(!draw.contains(85065666045).unwrap());
+            assert_eq!(draw.evaluate(5057791486).unwrap(), None);
+
+            let win = StateDatabase::new(dir.join("win"));
+            assert!(win.contains(85065666045).unwrap());
+            assert_eq!(win.evaluate(85065666045).unwrap(), Some(1));
+            let next_id = init_states[1].get_next_state(0).unwrap().get_id();
+            assert_eq!(win.evaluate(next_id).unwrap(), Some(0));
+
+            assert_eq!(draw.verdict(5057791486, 0).unwrap(), BoardStateEval::Draw);
+            assert_eq!(win.verdict(85065666045, 1).unwrap(), BoardStateEval::Win);
+            assert_eq!(win.verdict(85065666045, 0).unwrap(), BoardStateEval::Loss);
+            assert_eq!(win.verdict(next_id, 0).unwrap(), BoardStateEval::Win);
+            assert_eq!(
+                draw.verdict(85065666045, 1).unwrap(),
+                BoardStateEval::Unknown
+            );
+
+            let set = DatabaseSet::new(vec![draw, win, StateDatabase::new(dir.join("win"))]);
+            assert!(set.contains(5057791486).unwrap());
+            assert!(set.contains(85065666045).unwrap());
+            assert!(!set.contains(0).unwrap());
+            assert_eq!(set.evaluate(5057791486).unwrap(), None);
+            assert_eq!(set.evaluate(85065666045).unwrap(), Some(1));
+            assert!(matches!(
+                set.evaluate(0),
+                Err(DatabaseSetError::UnknownState { id: 0 })
+            ));
+
+            // Swapping the winning states of both players makes databases disagree.
+            let (win_dir, swapped_dir) = (dir.join("win"), dir.join("swapped"));
+            fs::create_dir(&swapped_dir).unwrap();
+            for (from, to) in [
+                ("all_states.data", "all_states.data"),
+                ("player_0_wins.data", "player_1_wins.data"),
+                ("player_1_wins.data", "player_0_wins.data"),
+            ] {
+                fs::copy(win_dir.join(from), swapped_dir.join(to)).unwrap();
+            }
+
+            let set = DatabaseSet::new(vec![
+                StateDatabase::new(&win_dir),
+                StateDatabase::new(&swapped_dir),
+            ]);
+            assert!(matches!(
+                set.evaluate(85065666045),
+                Err(DatabaseSetError::Conflict { id: 85065666045 })
+            ));
+
+            let mut rng = fastrand::Rng::with_seed(7);
+            let mut sampled_ids = HashSet::new();
+            let win = StateDatabase::new(&win_dir);
+            for _i in 0..100 {
+                let state = win
+                    .random_state(&Ruleset::OFFICIAL, &mut rng)
+                    .unwrap()
+                    .unwrap();
+                assert!(win.contains(state.get_id()).unwrap());
+                assert!(state.is_well_formed());
+                sampled_ids.insert(state.get_id());
+            }
+            assert!(sampled_ids.len() > 1);
+
+            // Data files with a prefix are distinguished by their names.
+            fs::copy(
+                win_dir.join("all_states.data"),
+                win_dir.join("w_all_states.data"),
+            )
+            .unwrap();
+            let prefixed = StateDatabase::with_prefix(&win_dir, "w");
+            assert!(prefixed.all_states_path().ends_with("w_all_states.data"));
+            assert!(prefixed.contains(85065666045).unwrap());
+            assert!(prefixed.evaluate(85065666045).is_err());
+
+            let missing_dir = dir.join("missing");
+            let set = DatabaseSet::new(vec![StateDatabase::new(&missing_dir)]);
+            assert!(StateDatabase::new(&missing_dir)
+                .random_state(&Ruleset::OFFICIAL, &mut rng)
+                .is_err());
+            assert!(matches!(
+                set.contains(85065666045),
+                Err(DatabaseSetError::DataError(DataError::IoError { .. }))
+            ));
+        });
+    }
+
+    #[test]
+    fn open_data_files() {
+        let init_state = BoardState::from(85065666045);
+        let next_id = init_state.get_next_state(0).unwrap().get_id();
+
+        file_operations::tests::with_tempdir(|dir| {
+            for (format, prefix) in [
+                (DataFormat::Zip, "zip"),
+                (DataFormat::Gzip, "gzip"),
+                (DataFormat::Roaring, "roaring"),
+            ] {
+                // Files missing on the first lookup are opened on the next one.
+                let database = StateDatabase::with_prefix(dir, prefix);
+                assert!(database.contains(85065666045).is_err());
+
+                generate_with_prefix(
+                    slice::from_ref(&init_state),
+                    format,
+                    Verbosity::Quiet,
+                    dir,
+                    prefix,
+                    None,
+                    true,
+                );
+                let expected = [
+                    BoardStateEval::Win,
+                    BoardStateEval::Loss,
+                    BoardStateEval::Unknown,
+                ];
+                let verdicts = |database: &StateDatabase| {
+                    [(85065666045, 1), (next_id, 1), (0, 0)]
+                        .map(|(id, player)| database.verdict(id, player).unwrap())
+                };
+                assert_eq!(verdicts(&database), expected);
+
+                // States looked up together get the same verdicts.
+                let ids = [next_id, 85065666045, 0, next_id];
+                for player in 0..=1 {
+                    let expected_verdicts: Vec<BoardStateEval> = ids
+                        .iter()
+                        .map(|&id| database.verdict(id, player).unwrap())
+                        .collect();
+                    assert_eq!(database.verdicts(&ids, player).unwrap(), expected_verdicts);
+                }
+
+                // Open files are still read after their names are removed, which only works on Unix.
+                if cfg!(unix) {
+                    for player in 0..=1 {
+                        fs::remove_file(database.winning_states_path(player)).unwrap();
+                    }
+                    fs::remove_file(database.all_states_path()).unwrap();
+                    assert_eq!(verdicts(&database), expected);
+                    assert!(StateDatabase::with_prefix(dir, prefix)
+                        .contains(85065666045)
+                        .is_err());
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn current_dir_database() {
+        // Data files of the current directory are found with their usual names.
+        file_operations::tests::run_in_tempdir(|| {
+            generate(
+                slice::from_ref(&BoardState::from(85065666045)),
+                DataFormat::Zip,
+                Verbosity::Normal,
+            );
+
+            let current = StateDatabase::in_current_dir();
+            assert_eq!(current.all_states_path(), file_operations::ALL_STATES_PATH);
+            assert!(current.contains(85065666045).unwrap());
+            assert!(current.is_winning(85065666045, 1).unwrap());
+            assert!(!current.is_winning(85065666045, 0).unwrap());
+        });
+    }
+}
@@ -1,103 +1,827 @@
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::{self, Read, Write};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::{error, fmt};
+
+use flate2::read::GzDecoder;
+use flate2::{Compression, GzBuilder};
 
 // Paths to data files.
 pub const WINNING_STATES_PATH: [&str; 2] = ["player_0_wins.data", "player_1_wins.data"];
 pub const ALL_STATES_PATH: &str = "all_states.data";
 
+/// Return `path` (one of the paths to data files) preceded by `prefix` and an underscore, or `path` itself if `prefix` is empty
+///
+/// Prefixes let several sets of data files be stored in the same directory.
+pub fn prefixed_path(prefix: &str, path: &str) -> String {
+    if prefix.is_empty() {
+        path.to_string()
+    } else {
+        format!("{}_{}", prefix, path)
+    }
+}
+
+/// Return the path to the data file `path` (one of the paths to data files) in `directory`, preceded by `prefix` (see `prefixed_path`)
+///
+/// An empty `directory` is the current directory.
+pub fn data_file_path(directory: impl AsRef<Path>, prefix: &str, path: &str) -> String {
+    directory
+        .as_ref()
+        .join(prefixed_path(prefix, path))
+        .to_string_lossy()
+        .into_owned()
+}
+
 const CHUNK_SIZE_BYTES: usize = 1024 * 1024;
-const CHUNK_SIZE_BITS: u64 = CHUNK_SIZE_BYTES as u64 * 8;
+/// Number of states stored in each chunk : the state `CHUNK_SIZE_BITS * chunk_id + bit_index` is stored in chunk `chunk_id`
+pub const CHUNK_SIZE_BITS: u64 = CHUNK_SIZE_BYTES as u64 * 8;
 
-/// Return the value of bit `state_id` from the ZIP-compressed chunked bit-set stored in file `path`
-pub fn read_state_value(path: &str, state_id: u64) -> bool {
-    let file = File::open(path)
-        .unwrap_or_else(|_| panic!("Unable to open file in read-only mode : {}", path));
+// First bytes of every gzip member.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
 
-    let mut zip_reader = zip::ZipArchive::new(file)
-        .unwrap_or_else(|_| panic!("Unable to parse ZIP file : {}", path));
+// Name of the gzip members storing the position of the index and the index itself.
+const GZIP_INDEX_NAME: &str = "index";
 
-    let chunk_id: u64 = state_id / CHUNK_SIZE_BITS;
-    let bit_index: u64 = state_id % CHUNK_SIZE_BITS;
-    let byte_index: u64 = bit_index / 8;
+// Identifier of the gzip extra subfield storing the position of the index.
+const GZIP_INDEX_SUBFIELD_ID: [u8; 2] = *b"SQ";
+
+// Each index entry is made of a chunk ID, a position in the file and a chunk size.
+const GZIP_INDEX_ENTRY_SIZE: usize = 3 * 8;
+
+// Values of the first 2 bytes of each serialized Roaring bitmap (which follow 12 bytes of a serialized treemap).
+const ROARING_COOKIES: [u16; 2] = [12346, 12347];
+
+/// Format of a data file
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum DataFormat {
+    /// ZIP archive with one file per chunk
+    Zip,
+
+    /// Gzip stream with one member per chunk, followed by an index of all chunks
+    Gzip,
+
+    /// Portable serialization format of Roaring bitmaps, which is entirely loaded in memory when opened
+    Roaring,
+}
+
+/// Error that occurred while reading or writing the data file `path`
+#[derive(Debug)]
+pub enum DataError {
+    /// The file could not be opened, read or written
+    IoError { path: String, source: io::Error },
+
+    /// The ZIP archive could not be parsed or written
+    ZipError {
+        path: String,
+        source: zip::result::ZipError,
+    },
+
+    /// The gzip index is missing or invalid
+    CorruptHeader { path: String },
+
+    /// The contents of chunk `chunk_id` could not be read
+    ChunkReadFailed {
+        path: String,
+        chunk_id: u64,
+        source: io::Error,
+    },
+}
+
+impl DataError {
+    fn io(path: &str, source: io::Error) -> Self {
+        Self::IoError {
+            path: path.to_string(),
+            source,
+        }
+    }
+
+    fn zip(path: &str, source: zip::result::ZipError) -> Self {
+        Self::ZipError {
+            path: path.to_string(),
+            source,
+        }
+    }
+
+    fn chunk_read_failed(path: &str, chunk_id: u64, source: io::Error) -> Self {
+        Self::ChunkReadFailed {
+            path: path.to_string(),
+            chunk_id,
+            source,
+        }
+    }
+}
+
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::IoError { path, source } => {
+                write!(f, "Unable to access data file : {} ({})", path, source)
+            }
+            Self::ZipError { path, source } => {
+                write!(f, "Unable to process ZIP file : {} ({})", path, source)
+            }
+            Self::CorruptHeader { path } => {
+                write!(f, "Unable to find index in gzip file : {}", path)
+            }
+            Self::ChunkReadFailed {
+                path,
+                chunk_id,
+                source,
+            } => write!(
+                f,
+                "Unable to read chunk {} from data file : {} ({})",
+                chunk_id, path, source
+            ),
+        }
+    }
+}
+
+impl error::Error for DataError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::IoError { source, .. } | Self::ChunkReadFailed { source, .. } => Some(source),
+            Self::ZipError { source, .. } => Some(source),
+            Self::CorruptHeader { .. } => None,
+        }
+    }
+}
+
+/// Return the value of bit `state_id` from the compressed chunked bit-set stored in file `path`
+///
+/// The format of the file is detected from its first bytes.
+pub fn read_state_value(path: &str, state_id: u64) -> Result<bool, DataError> {
+    let file = File::open(path).map_err(|e| DataError::io(path, e))?;
 
-    // Look for the chunk `chunk_id` in zip file.
-    let mut chunk_file = match zip_reader.by_name(&format!("chunk{chunk_id}")) {
-        Ok(f) => f,
-        Err(zip::result::ZipError::FileNotFound) => {
-            // The chunk is absent when it's only made of 0s.
-            return false;
+    read_state_value_from(file, path, state_id)
+}
+
+/// Same as `read_state_value`, but read the data file from `reader`, e.g. a buffer of embedded bytes
+///
+/// `name` stands for the path of the data file in errors.
+pub fn read_state_value_from(
+    reader: impl Read + Seek,
+    name: &str,
+    state_id: u64,
+) -> Result<bool, DataError> {
+    StateReader::new(reader, name)?.contains(state_id)
+}
+
+/// Same as `read_state_value`, but terminate thread on error
+pub fn read_state_value_or_abort(path: &str, state_id: u64) -> bool {
+    read_state_value(path, state_id).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Data file kept open to look up many states, e.g. during a game
+///
+/// The central directory of ZIP archives and the index of gzip files are only parsed when the file is opened,
+/// and Roaring files are entirely loaded at that time.
+pub struct StateReader<R = File> {
+    path: String,
+    source: ReaderSource<R>,
+}
+
+// Where states are looked up, depending on the format of the data file.
+enum ReaderSource<R> {
+    Zip(zip::ZipArchive<R>),
+    Gzip {
+        file: R,
+        // Position in the file and size of each chunk, given its ID.
+        index: HashMap<u64, (u64, u64)>,
+    },
+    Roaring(roaring::RoaringTreemap),
+}
+
+impl StateReader {
+    /// Open the data file `path`, whose format is detected from its first bytes
+    pub fn open(path: &str) -> Result<Self, DataError> {
+        let file = File::open(path).map_err(|e| DataError::io(path, e))?;
+
+        Self::new(file, path)
+    }
+}
+
+impl<R: Read + Seek> StateReader<R> {
+    /// Same as `open`, but read the data file from `reader`, e.g. a buffer of embedded bytes
+    ///
+    /// `name` stands for the path of the data file in errors.
+    pub fn new(mut reader: R, name: &str) -> Result<Self, DataError> {
+        let source = match detect_format(&mut reader, name)? {
+            DataFormat::Zip => ReaderSource::Zip(
+                zip::ZipArchive::new(reader).map_err(|e| DataError::zip(name, e))?,
+            ),
+            DataFormat::Gzip => ReaderSource::Gzip {
+                index: read_gzip_index(&mut reader, name)?
+                    .into_iter()
+                    .map(|(chunk_id, chunk_position, chunk_size)| {
+                        (chunk_id, (chunk_position, chunk_size))
+                    })
+                    .collect(),
+                file: reader,
+            },
+            DataFormat::Roaring => ReaderSource::Roaring(read_states_from_roaring(reader, name)?),
+        };
+
+        Ok(StateReader {
+            path: name.to_string(),
+            source,
+        })
+    }
+
+    /// Return the value of bit `state_id` from the data file, i.e. whether it contains the state `state_id`
+    pub fn contains(&mut self, state_id: u64) -> Result<bool, DataError> {
+        let path = &self.path;
+
+        let chunk_id: u64 = state_id / CHUNK_SIZE_BITS;
+        let bit_index: u64 = state_id % CHUNK_SIZE_BITS;
+        let byte_index: u64 = bit_index / 8;
+
+        match &mut self.source {
+            ReaderSource::Zip(zip_reader) => {
+                // Look for the chunk `chunk_id` in zip file.
+                let mut chunk_file = match zip_reader.by_name(&format!("chunk{chunk_id}")) {
+                    Ok(f) => f,
+                    Err(zip::result::ZipError::FileNotFound) => {
+                        // The chunk is absent when it's only made of 0s.
+                        return Ok(false);
+                    }
+                    Err(e) => return Err(DataError::zip(path, e)),
+                };
+
+                if byte_index >= chunk_file.size() {
+                    // `byte_index` is part of (removed) 0s at the end of the chunk.
+                    return Ok(false);
+                }
+
+                read_bit_from_chunk(&mut chunk_file, bit_index)
+                    .map_err(|e| DataError::chunk_read_failed(path, chunk_id, e))
+            }
+            ReaderSource::Gzip { file, index } => {
+                // Look for the chunk `chunk_id` in the index.
+                let Some(&(chunk_position, chunk_size)) = index.get(&chunk_id) else {
+                    // The chunk is absent when it's only made of 0s.
+                    return Ok(false);
+                };
+
+                if byte_index >= chunk_size {
+                    // `byte_index` is part of (removed) 0s at the end of the chunk.
+                    return Ok(false);
+                }
+
+                file.seek(SeekFrom::Start(chunk_position))
+                    .and_then(|_| read_bit_from_chunk(&mut GzDecoder::new(&mut *file), bit_index))
+                    .map_err(|e| DataError::chunk_read_failed(path, chunk_id, e))
+            }
+            ReaderSource::Roaring(states) => Ok(states.contains(state_id)),
         }
-        Err(_) => panic!(
-            "Unable to look for chunk {} in ZIP file : {}",
-            chunk_id, path
-        ),
+    }
+
+    /// Same as `contains`, but for each state of `state_ids`, decompressing each chunk at most once
+    pub fn contains_all(&mut self, state_ids: &[u64]) -> Result<Vec<bool>, DataError> {
+        if let ReaderSource::Roaring(states) = &self.source {
+            return Ok(state_ids.iter().map(|&id| states.contains(id)).collect());
+        }
+
+        // Indices of `state_ids`, sorted so that states of the same chunk are consecutive.
+        let mut indices: Vec<usize> = (0..state_ids.len()).collect();
+        indices.sort_unstable_by_key(|&index| state_ids[index]);
+
+        let mut contained = vec![false; state_ids.len()];
+        for chunk_indices in indices
+            .chunk_by(|&a, &b| state_ids[a] / CHUNK_SIZE_BITS == state_ids[b] / CHUNK_SIZE_BITS)
+        {
+            let chunk_id = state_ids[chunk_indices[0]] / CHUNK_SIZE_BITS;
+            let Some(chunk_buffer) = self.read_chunk(chunk_id)? else {
+                continue;
+            };
+
+            for &index in chunk_indices {
+                let bit_index = state_ids[index] % CHUNK_SIZE_BITS;
+                // Bytes past the end of the buffer are (removed) 0s at the end of the chunk.
+                contained[index] = chunk_buffer
+                    .get((bit_index / 8) as usize)
+                    .is_some_and(|byte| (byte >> (bit_index % 8)) & 1 == 1);
+            }
+        }
+
+        Ok(contained)
+    }
+
+    /// Return a state of the data file selected at random, or `None` if the data file is empty
+    ///
+    /// A chunk is selected with a probability proportional to its size, then a state among those of that chunk,
+    /// so only that chunk is decompressed. The selection is only uniform among all states if chunks have similar densities.
+    pub fn random_state(&mut self, rng: &mut fastrand::Rng) -> Result<Option<u64>, DataError> {
+        let path = &self.path;
+
+        // Chunks as (chunk ID, size) pairs.
+        let mut chunks: Vec<(u64, u64)> = match &mut self.source {
+            ReaderSource::Zip(zip_reader) => (0..zip_reader.len())
+                .filter_map(|index| match zip_reader.by_index_raw(index) {
+                    Ok(chunk_file) => parse_chunk_name(chunk_file.name())
+                        .map(|chunk_id| Ok((chunk_id, chunk_file.size()))),
+                    Err(e) => Some(Err(DataError::zip(path, e))),
+                })
+                .collect::<Result<_, _>>()?,
+            ReaderSource::Gzip { index, .. } => index
+                .iter()
+                .map(|(&chunk_id, &(_, chunk_size))| (chunk_id, chunk_size))
+                .collect(),
+            ReaderSource::Roaring(states) => {
+                // All states are already in memory.
+                return Ok(match states.len() {
+                    0 => None,
+                    len => states.select(rng.u64(0..len)),
+                });
+            }
+        };
+        // The order of the gzip index is arbitrary, and must not change the selected chunk.
+        chunks.sort_unstable();
+
+        let total_size: u64 = chunks.iter().map(|&(_, chunk_size)| chunk_size).sum();
+        if total_size == 0 {
+            return Ok(None);
+        }
+
+        let mut offset = rng.u64(0..total_size);
+        let &(chunk_id, _) = chunks
+            .iter()
+            .find(|&&(_, chunk_size)| {
+                if offset < chunk_size {
+                    true
+                } else {
+                    offset -= chunk_size;
+                    false
+                }
+            })
+            .expect("The offset should be lower than the total size of the chunks");
+
+        let chunk_buffer = self
+            .read_chunk(chunk_id)?
+            .expect("The selected chunk should be in the data file");
+
+        // Chunks written by `write_states` hold at least one state.
+        let states_count = chunk_buffer
+            .iter()
+            .map(|byte| byte.count_ones() as usize)
+            .sum();
+        if states_count == 0 {
+            return Ok(None);
+        }
+
+        let state_id = chunk_state_ids(&chunk_buffer, chunk_id).nth(rng.usize(0..states_count));
+        Ok(state_id)
+    }
+
+    /// Return the decompressed contents of chunk `chunk_id`, or `None` if it is absent because it's only made of 0s
+    ///
+    /// Roaring files have no chunks, and must be queried directly.
+    fn read_chunk(&mut self, chunk_id: u64) -> Result<Option<Vec<u8>>, DataError> {
+        let path = &self.path;
+        let mut chunk_buffer = Vec::new();
+
+        match &mut self.source {
+            ReaderSource::Zip(zip_reader) => {
+                match zip_reader.by_name(&format!("chunk{chunk_id}")) {
+                    Ok(mut chunk_file) => chunk_file.read_to_end(&mut chunk_buffer),
+                    Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+                    Err(e) => return Err(DataError::zip(path, e)),
+                }
+            }
+            ReaderSource::Gzip { file, index } => {
+                let Some(&(chunk_position, _)) = index.get(&chunk_id) else {
+                    return Ok(None);
+                };
+
+                file.seek(SeekFrom::Start(chunk_position))
+                    .and_then(|_| GzDecoder::new(&mut *file).read_to_end(&mut chunk_buffer))
+            }
+            ReaderSource::Roaring(_) => unreachable!("Roaring files have no chunks"),
+        }
+        .map_err(|e| DataError::chunk_read_failed(path, chunk_id, e))?;
+
+        Ok(Some(chunk_buffer))
+    }
+}
+
+/// Return the format of `file`, previously opened from `path`
+fn detect_format(file: &mut (impl Read + Seek), path: &str) -> Result<DataFormat, DataError> {
+    let mut header = Vec::new();
+    Read::take(&mut *file, 14)
+        .read_to_end(&mut header)
+        .map_err(|e| DataError::io(path, e))?;
+
+    // Files which are neither gzip nor Roaring files are left to the ZIP parser.
+    let format = if header.starts_with(&GZIP_MAGIC) {
+        DataFormat::Gzip
+    } else if header == [0; 8]
+        || (header.len() == 14
+            && ROARING_COOKIES.contains(&u16::from_le_bytes([header[12], header[13]])))
+    {
+        // An empty treemap is only made of its number of bitmaps.
+        DataFormat::Roaring
+    } else {
+        DataFormat::Zip
     };
 
-    if byte_index >= chunk_file.size() {
-        // `byte_index` is part of (removed) 0s at the end of the chunk.
-        return false;
+    file.rewind().map_err(|e| DataError::io(path, e))?;
+
+    Ok(format)
+}
+
+/// Return the entries of the index of the gzip `file`, as (chunk ID, position in the file, chunk size) tuples
+fn read_gzip_index(
+    file: &mut (impl Read + Seek),
+    path: &str,
+) -> Result<Vec<(u64, u64, u64)>, DataError> {
+    // The first member is empty, its header only holds the position of the index.
+    let mut index_position_decoder = GzDecoder::new(&mut *file);
+    index_position_decoder
+        .read_to_end(&mut Vec::new())
+        .map_err(|e| DataError::io(path, e))?;
+    let index_position = index_position_decoder
+        .header()
+        .and_then(|header| header.extra())
+        .and_then(parse_gzip_index_position)
+        .ok_or_else(|| DataError::CorruptHeader {
+            path: path.to_string(),
+        })?;
+
+    // Read the whole index, which is the last member.
+    let mut index = Vec::new();
+    file.seek(SeekFrom::Start(index_position))
+        .and_then(|_| GzDecoder::new(&mut *file).read_to_end(&mut index))
+        .map_err(|e| DataError::io(path, e))?;
+
+    Ok(index
+        .chunks_exact(GZIP_INDEX_ENTRY_SIZE)
+        .map(|entry| {
+            let mut fields = entry.chunks_exact(8).map(|field| {
+                u64::from_le_bytes(field.try_into().expect("Fields are 8 bytes long"))
+            });
+            let mut next_field = || fields.next().expect("Entries have 3 fields");
+            (next_field(), next_field(), next_field())
+        })
+        .collect())
+}
+
+/// Return all states stored in the compressed chunked bit-set of file `path`
+///
+/// The format of the file is detected from its first bytes.
+pub fn read_states(path: &str) -> Result<roaring::RoaringTreemap, DataError> {
+    let file = File::open(path).map_err(|e| DataError::io(path, e))?;
+
+    read_states_from(file, path)
+}
+
+/// Same as `read_states`, but read the data file from `reader`, e.g. a buffer of embedded bytes
+///
+/// `name` stands for the path of the data file in errors.
+pub fn read_states_from(
+    mut reader: impl Read + Seek,
+    name: &str,
+) -> Result<roaring::RoaringTreemap, DataError> {
+    let format = detect_format(&mut reader, name)?;
+    if format == DataFormat::Roaring {
+        return read_states_from_roaring(reader, name);
     }
 
+    let mut states = roaring::RoaringTreemap::new();
+    for chunk in Chunks::new(reader, name, format)? {
+        let (chunk_id, chunk_buffer) = chunk?;
+        add_chunk_states(&mut states, &chunk_buffer, chunk_id);
+    }
+
+    Ok(states)
+}
+
+/// Return an iterator over the chunks stored in file `path`, as (chunk ID, decompressed contents) pairs
+///
+/// Chunks only made of 0s are absent from the file and skipped. The contents of each chunk may lack its trailing 0s.
+/// Each chunk can be processed independently, e.g. by a different thread.
+pub fn read_chunks(path: &str) -> Result<Chunks, DataError> {
+    let mut file = File::open(path).map_err(|e| DataError::io(path, e))?;
+    let format = detect_format(&mut file, path)?;
+
+    Chunks::new(file, path, format)
+}
+
+/// Iterator over the chunks of a data file read from `R`, returned by `read_chunks`
+pub struct Chunks<R = File> {
+    path: String,
+    source: ChunkSource<R>,
+}
+
+// Where the next chunks are read from, depending on the format of the data file.
+enum ChunkSource<R> {
+    Zip {
+        zip_reader: zip::ZipArchive<R>,
+        next_index: usize,
+    },
+    Gzip {
+        file: R,
+        index: std::vec::IntoIter<(u64, u64, u64)>,
+    },
+    // Roaring files don't have chunks, which are built from all states.
+    Roaring(std::vec::IntoIter<(u64, Vec<u8>)>),
+}
+
+impl<R: Read + Seek> Chunks<R> {
+    fn new(mut file: R, path: &str, format: DataFormat) -> Result<Self, DataError> {
+        let source = match format {
+            DataFormat::Zip => ChunkSource::Zip {
+                zip_reader: zip::ZipArchive::new(file).map_err(|e| DataError::zip(path, e))?,
+                next_index: 0,
+            },
+            DataFormat::Gzip => ChunkSource::Gzip {
+                index: read_gzip_index(&mut file, path)?.into_iter(),
+                file,
+            },
+            DataFormat::Roaring => {
+                let mut chunks = Vec::new();
+                let result: Result<(), ()> = for_each_chunk(
+                    &read_states_from_roaring(file, path)?,
+                    |chunk_buffer, chunk_id| {
+                        chunks.push((chunk_id, chunk_buffer.to_vec()));
+                        Ok(())
+                    },
+                );
+                result.expect("Collecting chunks can't fail");
+
+                ChunkSource::Roaring(chunks.into_iter())
+            }
+        };
+
+        Ok(Chunks {
+            path: path.to_string(),
+            source,
+        })
+    }
+}
+
+impl<R: Read + Seek> Iterator for Chunks<R> {
+    type Item = Result<(u64, Vec<u8>), DataError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let path = &self.path;
+
+        match &mut self.source {
+            ChunkSource::Zip {
+                zip_reader,
+                next_index,
+            } => {
+                while *next_index < zip_reader.len() {
+                    let index = *next_index;
+                    *next_index += 1;
+
+                    let mut chunk_file = match zip_reader.by_index(index) {
+                        Ok(f) => f,
+                        Err(e) => return Some(Err(DataError::zip(path, e))),
+                    };
+
+                    // Files which are not chunks are ignored.
+                    let Some(chunk_id) = parse_chunk_name(chunk_file.name()) else {
+                        continue;
+                    };
+
+                    let mut chunk_buffer = Vec::new();
+                    return Some(
+                        chunk_file
+                            .read_to_end(&mut chunk_buffer)
+                            .map(|_| (chunk_id, chunk_buffer))
+                            .map_err(|e| DataError::chunk_read_failed(path, chunk_id, e)),
+                    );
+                }
+
+                None
+            }
+            ChunkSource::Gzip { file, index } => {
+                let (chunk_id, chunk_position, _) = index.next()?;
+
+                let mut chunk_buffer = Vec::new();
+                Some(
+                    file.seek(SeekFrom::Start(chunk_position))
+                        .and_then(|_| GzDecoder::new(&mut *file).read_to_end(&mut chunk_buffer))
+                        .map(|_| (chunk_id, chunk_buffer))
+                        .map_err(|e| DataError::chunk_read_failed(path, chunk_id, e)),
+                )
+            }
+            ChunkSource::Roaring(chunks) => chunks.next().map(Ok),
+        }
+    }
+}
+
+/// Return the ID of the chunk stored in the file `name` of a ZIP archive, or `None` if it isn't a chunk
+fn parse_chunk_name(name: &str) -> Option<u64> {
+    name.strip_prefix("chunk").and_then(|id| id.parse().ok())
+}
+
+/// Return all states of the Roaring `file`
+fn read_states_from_roaring(
+    file: impl Read,
+    path: &str,
+) -> Result<roaring::RoaringTreemap, DataError> {
+    roaring::RoaringTreemap::deserialize_from(io::BufReader::new(file))
+        .map_err(|e| DataError::io(path, e))
+}
+
+/// Insert into `states` the states whose bits are set in the contents of chunk `chunk_id`
+fn add_chunk_states(states: &mut roaring::RoaringTreemap, chunk_buffer: &[u8], chunk_id: u64) {
+    states.extend(chunk_state_ids(chunk_buffer, chunk_id));
+}
+
+/// Return an iterator over the states whose bits are set in the contents of chunk `chunk_id`, in increasing order
+pub fn chunk_state_ids(chunk_buffer: &[u8], chunk_id: u64) -> impl Iterator<Item = u64> + '_ {
+    chunk_buffer
+        .iter()
+        .enumerate()
+        .flat_map(move |(byte_index, &byte)| {
+            (0..8)
+                .filter(move |bit| (byte >> bit) & 1 == 1)
+                .map(move |bit| chunk_id * CHUNK_SIZE_BITS + byte_index as u64 * 8 + bit)
+        })
+}
+
+/// Return the value of the bit `bit_index` from the decompressed contents of a chunk
+fn read_bit_from_chunk(chunk_reader: &mut impl Read, bit_index: u64) -> io::Result<bool> {
+    let byte_index: u64 = bit_index / 8;
+
     if byte_index > 0 {
         // Drop the first `byte_index` bytes from the chunk.
-        io::copy(&mut chunk_file.by_ref().take(byte_index), &mut io::sink()).unwrap_or_else(|_| {
-            panic!(
-                "Unable to skip the first {} bytes from chunk {} in ZIP file : {}",
-                byte_index, chunk_id, path
-            )
-        });
+        io::copy(&mut chunk_reader.take(byte_index), &mut io::sink())?;
     }
 
     // Read the value of the byte `byte_index` from the chunk.
     let mut buffer = [0u8];
-    chunk_file.read_exact(&mut buffer).unwrap_or_else(|_| {
-        panic!(
-            "Unable to read byte {} from chunk {} in ZIP file : {}",
-            byte_index, chunk_id, path
-        )
-    });
+    chunk_reader.read_exact(&mut buffer)?;
 
     // Return the value of the bit `bit_index` from the chunk.
-    (buffer[0] >> (bit_index % 8)) & 1 == 1
+    Ok((buffer[0] >> (bit_index % 8)) & 1 == 1)
 }
 
-/// Store `states` in a ZIP-compressed chunked bit-set file `path`
-pub fn write_states(path: &str, states: &roaring::RoaringTreemap) {
-    // Create a new file and open it in r+w mode.
-    let file = File::options()
-        .read(true)
-        .write(true)
-        .create_new(true)
-        .open(path)
-        .unwrap_or_else(|_| panic!("Unable to create file : {}", path));
+/// Store `states` in a compressed chunked bit-set written to `file`, previously created at `path`
+pub fn write_states(
+    file: File,
+    path: &str,
+    states: &roaring::RoaringTreemap,
+    format: DataFormat,
+) -> Result<(), DataError> {
+    match format {
+        DataFormat::Zip => write_states_to_zip(file, path, states),
+        DataFormat::Gzip => write_states_to_gzip(file, path, states),
+        DataFormat::Roaring => {
+            let mut writer = io::BufWriter::new(file);
+            states
+                .serialize_into(&mut writer)
+                .and_then(|_| writer.flush())
+                .map_err(|e| DataError::io(path, e))
+        }
+    }
+}
+
+/// Same as `write_states`, but terminate thread on error
+pub fn write_states_or_abort(
+    file: File,
+    path: &str,
+    states: &roaring::RoaringTreemap,
+    format: DataFormat,
+) {
+    write_states(file, path, states, format).unwrap_or_else(|e| panic!("{}", e));
+}
 
+/// Store `states` in a ZIP-compressed chunked bit-set written to `file`
+fn write_states_to_zip(
+    file: File,
+    path: &str,
+    states: &roaring::RoaringTreemap,
+) -> Result<(), DataError> {
     // Create an empty ZIP file.
     let mut zip_writer = zip::ZipWriter::new(&file);
 
-    let mut add_chunk = |chunk_buffer: &[u8], chunk_id: u64| {
+    for_each_chunk(states, |chunk_buffer, chunk_id| {
         // Add a chunk (new file) to the ZIP file.
         zip_writer
             .start_file(
                 format!("chunk{chunk_id}"),
                 zip::write::SimpleFileOptions::default(),
             )
-            .unwrap_or_else(|_| {
-                panic!("Unable to create chunk {} in ZIP file : {}", chunk_id, path)
-            });
+            .map_err(|e| DataError::zip(path, e))?;
 
         // Add chunk contents.
         zip_writer
             .write_all(chunk_buffer)
-            .unwrap_or_else(|_| panic!("Unable to add chunk {} to ZIP file : {}", chunk_id, path));
-    };
+            .map_err(|e| DataError::io(path, e))
+    })?;
+
+    // Finalize ZIP file.
+    zip_writer.finish().map_err(|e| DataError::zip(path, e))?;
+
+    Ok(())
+}
+
+/// Store `states` in a gzip-compressed chunked bit-set written to `file`
+///
+/// The file is a sequence of gzip members : the position of the index, one member per chunk and the index.
+fn write_states_to_gzip(
+    mut file: File,
+    path: &str,
+    states: &roaring::RoaringTreemap,
+) -> Result<(), DataError> {
+    // Reserve space for the position of the index, which is only known at the end.
+    write_gzip_member(&mut file, GZIP_INDEX_NAME, Some(0), &[])
+        .map_err(|e| DataError::io(path, e))?;
 
+    let mut index: Vec<u8> = Vec::new();
+
+    for_each_chunk(states, |chunk_buffer, chunk_id| {
+        let chunk_position = file.stream_position()?;
+
+        // Add a chunk (new member) to the gzip file.
+        write_gzip_member(&mut file, &format!("chunk{chunk_id}"), None, chunk_buffer)?;
+
+        for field in [chunk_id, chunk_position, chunk_buffer.len() as u64] {
+            index.extend(field.to_le_bytes());
+        }
+
+        Ok(())
+    })
+    .map_err(|e: io::Error| DataError::io(path, e))?;
+
+    let index_position = file.stream_position().map_err(|e| DataError::io(path, e))?;
+    write_gzip_member(&mut file, GZIP_INDEX_NAME, None, &index)
+        .map_err(|e| DataError::io(path, e))?;
+
+    // Overwrite the first member, which keeps the same size.
+    file.rewind()
+        .and_then(|_| write_gzip_member(&mut file, GZIP_INDEX_NAME, Some(index_position), &[]))
+        .map_err(|e| DataError::io(path, e))
+}
+
+/// Write a gzip member named `name` with contents `data` to `file`
+///
+/// When `index_position_opt` is provided, it is stored in the extra field of the member's header.
+fn write_gzip_member(
+    file: &mut File,
+    name: &str,
+    index_position_opt: Option<u64>,
+    data: &[u8],
+) -> io::Result<()> {
+    let mut builder = GzBuilder::new().filename(name);
+
+    if let Some(index_position) = index_position_opt {
+        // Extra subfield made of its ID, the length of its data (2 bytes) and the data.
+        let mut extra = GZIP_INDEX_SUBFIELD_ID.to_vec();
+        extra.extend(8u16.to_le_bytes());
+        extra.extend(index_position.to_le_bytes());
+        builder = builder.extra(extra);
+    }
+
+    let mut encoder = builder.write(file, Compression::default());
+    encoder.write_all(data)?;
+    encoder.try_finish()
+}
+
+/// Return the position of the index stored in the `extra` field of a gzip header
+fn parse_gzip_index_position(extra: &[u8]) -> Option<u64> {
+    if extra.len() != 12 || extra[..2] != GZIP_INDEX_SUBFIELD_ID {
+        return None;
+    }
+
+    Some(u64::from_le_bytes(extra[4..].try_into().ok()?))
+}
+
+/// Return the number of chunks and the total size in bytes of their contents, as stored by `write_states`
+///
+/// The size is measured before compression.
+pub fn get_chunks_size(states: &roaring::RoaringTreemap) -> (u64, u64) {
+    let mut chunks_count: u64 = 0;
+    let mut chunks_size: u64 = 0;
+
+    let result: Result<(), ()> = for_each_chunk(states, |chunk_buffer, _chunk_id| {
+        chunks_count += 1;
+        chunks_size += chunk_buffer.len() as u64;
+        Ok(())
+    });
+    result.expect("Counting chunks can't fail");
+
+    (chunks_count, chunks_size)
+}
+
+/// Split `states` into chunked bit-sets and call `add_chunk` with the bytes and ID of each non-empty chunk
+///
+/// Chunks are provided in increasing ID order, without their trailing 0s.
+/// Stop at the first error returned by `add_chunk`.
+fn for_each_chunk<E>(
+    states: &roaring::RoaringTreemap,
+    mut add_chunk: impl FnMut(&[u8], u64) -> Result<(), E>,
+) -> Result<(), E> {
     let mut chunk_buffer: Vec<u8> = Vec::with_capacity(CHUNK_SIZE_BYTES);
     let mut chunk_id: u64 = states.min().unwrap_or(0) / CHUNK_SIZE_BITS;
 
     for state_id in states.iter() {
         // Write `chunk_buffer` before it grows larger than `CHUNK_SIZE_BYTES`.
         if state_id / CHUNK_SIZE_BITS > chunk_id {
-            add_chunk(&chunk_buffer, chunk_id);
+            add_chunk(&chunk_buffer, chunk_id)?;
             chunk_buffer = Vec::with_capacity(CHUNK_SIZE_BYTES);
             chunk_id = state_id / CHUNK_SIZE_BITS;
         }
@@ -115,20 +839,40 @@ pub fn write_states(path: &str, states: &roaring::RoaringTreemap) {
     }
 
     if !chunk_buffer.is_empty() {
-        add_chunk(&chunk_buffer, chunk_id);
+        add_chunk(&chunk_buffer, chunk_id)?;
     }
 
-    // Finalize ZIP file.
-    zip_writer
-        .finish()
-        .unwrap_or_else(|_| panic!("Unable to finalize ZIP file : {}", path));
+    Ok(())
 }
 
-/// Terminate thread if `path` is an existing path in the file system
-pub fn abort_if_path_exists(path: &str) {
-    if std::path::Path::new(path).exists() {
-        panic!("The following path already exists : {}\nThe process will be stopped now to avoid losing data!", path);
-    }
+/// Create a new file `path` and open it in r+w mode
+///
+/// Terminate thread if `path` is an existing path in the file system.
+/// The check and the creation are a single atomic operation, so concurrent processes can't overwrite each other's files.
+pub fn create_new_file(path: &str) -> File {
+    File::options()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .unwrap_or_else(|e| match e.kind() {
+            io::ErrorKind::AlreadyExists => panic!("The following path already exists : {}\nThe process will be stopped now to avoid losing data!", path),
+            _ => panic!("Unable to create file : {}", path),
+        })
+}
+
+/// Same as `create_new_file`, but truncate `path` if it exists instead of terminating thread
+///
+/// This is dangerous since existing data is lost without warning. It is only meant for paths managed by the caller,
+/// such as named pipes.
+pub fn overwrite_file(path: &str) -> File {
+    File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .unwrap_or_else(|_| panic!("Unable to create file : {}", path))
 }
 
 #[cfg(test)]
@@ -137,6 +881,10 @@ pub mod tests {
 
     use super::*;
 
+    /// Run `f` in a new temporary directory, which becomes the current directory
+    ///
+    /// Only tests of the current directory defaults need it, since they can't run in parallel.
+    /// Other tests should pass the directory explicitly with `with_tempdir`.
     pub fn run_in_tempdir<F>(f: F)
     where
         F: FnOnce(),
@@ -159,13 +907,27 @@ pub mod tests {
         tmp.close().unwrap();
     }
 
+    /// Run `f` with the path to a new temporary directory, without changing the current directory
+    pub fn with_tempdir<F>(f: F)
+    where
+        F: FnOnce(&Path),
+    {
+        let tmp = tempfile::TempDir::new().unwrap();
+
+        f(tmp.path());
+
+        tmp.close().unwrap();
+    }
+
     #[test]
     fn state_from_zip() {
-        run_in_tempdir(|| {
+        with_tempdir(|dir| {
+            let path = data_file_path(dir, "", "f");
+
             let file = File::options()
                 .write(true)
                 .create_new(true)
-                .open("f")
+                .open(&path)
                 .unwrap();
 
             let mut zip = zip::ZipWriter::new(&file);
@@ -187,7 +949,7 @@ pub mod tests {
                     .chain(chunk_end_bit - at_max_100_bits..chunk_end_bit)
                 {
                     assert!(
-                        read_state_value("f", i) == (i == 17 * CHUNK_SIZE_BITS + 7)
+                        read_state_value(&path, i).unwrap() == (i == 17 * CHUNK_SIZE_BITS + 7)
                             || (i == 17 * CHUNK_SIZE_BITS + 24)
                             || (i == 27)
                             || (16..24).contains(&i)
@@ -213,10 +975,18 @@ pub mod tests {
             roaring::RoaringTreemap::from_sorted_iter(marked_ids).unwrap()
         };
 
-        run_in_tempdir(|| {
-            write_states("states", &states);
+        with_tempdir(|dir| {
+            let states_path = data_file_path(dir, "", "states");
+
+            write_states(
+                create_new_file(&states_path),
+                &states_path,
+                &states,
+                DataFormat::Zip,
+            )
+            .unwrap();
 
-            let mut zip = zip::ZipArchive::new(File::open("states").unwrap()).unwrap();
+            let mut zip = zip::ZipArchive::new(File::open(&states_path).unwrap()).unwrap();
             for i in 0..zip.len() {
                 let mut file = zip.by_index(i).unwrap();
                 let file_name = file.name();
@@ -243,33 +1013,336 @@ pub mod tests {
 
     #[test]
     fn states_empty_to_zip() {
-        run_in_tempdir(|| {
-            write_states("states", &roaring::RoaringTreemap::new());
+        with_tempdir(|dir| {
+            let states_path = data_file_path(dir, "", "states");
 
-            let zip = zip::ZipArchive::new(File::open("states").unwrap()).unwrap();
+            write_states(
+                create_new_file(&states_path),
+                &states_path,
+                &roaring::RoaringTreemap::new(),
+                DataFormat::Zip,
+            )
+            .unwrap();
+
+            let zip = zip::ZipArchive::new(File::open(&states_path).unwrap()).unwrap();
 
             assert!(zip.is_empty());
-            assert!(!read_state_value("states", 0));
-            assert!(!read_state_value("states", 1));
-            assert!(!read_state_value("states", u64::MAX));
+            assert!(!read_state_value(&states_path, 0).unwrap());
+            assert!(!read_state_value(&states_path, 1).unwrap());
+            assert!(!read_state_value(&states_path, u64::MAX).unwrap());
         });
     }
 
     #[test]
     fn states_unique_to_zip() {
-        run_in_tempdir(|| {
+        with_tempdir(|dir| {
+            let states_path = data_file_path(dir, "", "states");
+
             write_states(
-                "states",
+                create_new_file(&states_path),
+                &states_path,
                 &roaring::RoaringTreemap::from_sorted_iter([u64::MAX]).unwrap(),
-            );
+                DataFormat::Zip,
+            )
+            .unwrap();
 
-            let zip = zip::ZipArchive::new(File::open("states").unwrap()).unwrap();
+            let zip = zip::ZipArchive::new(File::open(&states_path).unwrap()).unwrap();
 
             assert_eq!(zip.len(), 1);
-            assert!(!read_state_value("states", 0));
-            assert!(!read_state_value("states", 1));
-            assert!(!read_state_value("states", u64::MAX - 1));
-            assert!(read_state_value("states", u64::MAX));
+            assert!(!read_state_value(&states_path, 0).unwrap());
+            assert!(!read_state_value(&states_path, 1).unwrap());
+            assert!(!read_state_value(&states_path, u64::MAX - 1).unwrap());
+            assert!(read_state_value(&states_path, u64::MAX).unwrap());
+        });
+    }
+
+    #[test]
+    fn states_to_gzip() {
+        let mut states = roaring::RoaringTreemap::new();
+
+        for chunk_id in [0, 1, 17, 314] {
+            for _i in 0..9 {
+                states.insert(chunk_id * CHUNK_SIZE_BITS + fastrand::u64(0..CHUNK_SIZE_BITS));
+            }
+        }
+        states.insert(0);
+        states.insert(u64::MAX);
+
+        with_tempdir(|dir| {
+            let zip_path = data_file_path(dir, "", "zip");
+            let gzip_path = data_file_path(dir, "", "gzip");
+            let roaring_path = data_file_path(dir, "", "roaring");
+
+            for (path, format) in [
+                (&zip_path, DataFormat::Zip),
+                (&gzip_path, DataFormat::Gzip),
+                (&roaring_path, DataFormat::Roaring),
+            ] {
+                write_states(create_new_file(path), path, &states, format).unwrap();
+
+                let mut file = File::open(path).unwrap();
+                assert_eq!(detect_format(&mut file, path).unwrap(), format);
+
+                assert_eq!(read_states(path).unwrap(), states);
+
+                // IDs can be rebuilt from each chunk independently.
+                let mut chunk_ids = Vec::new();
+                let mut chunk_states = roaring::RoaringTreemap::new();
+                for chunk in read_chunks(path).unwrap() {
+                    let (chunk_id, chunk_buffer) = chunk.unwrap();
+                    assert!(chunk_buffer.len() <= CHUNK_SIZE_BYTES);
+                    chunk_ids.push(chunk_id);
+                    add_chunk_states(&mut chunk_states, &chunk_buffer, chunk_id);
+                }
+                assert_eq!(chunk_ids, vec![0, 1, 17, 314, u64::MAX / CHUNK_SIZE_BITS]);
+                assert_eq!(chunk_states, states);
+
+                // The same data can be read from memory.
+                let bytes = std::fs::read(path).unwrap();
+                assert_eq!(
+                    read_states_from(io::Cursor::new(&bytes), path).unwrap(),
+                    states
+                );
+                for id in [0, 1, 17 * CHUNK_SIZE_BITS, u64::MAX] {
+                    assert_eq!(
+                        read_state_value_from(io::Cursor::new(&bytes), path, id).unwrap(),
+                        states.contains(id)
+                    );
+                }
+            }
+
+            for state_id in states.iter() {
+                for id in [
+                    state_id.saturating_sub(1),
+                    state_id,
+                    state_id.saturating_add(1),
+                ] {
+                    assert_eq!(
+                        read_state_value(&zip_path, id).unwrap(),
+                        states.contains(id)
+                    );
+                    assert_eq!(
+                        read_state_value(&gzip_path, id).unwrap(),
+                        states.contains(id)
+                    );
+                    assert_eq!(
+                        read_state_value(&roaring_path, id).unwrap(),
+                        states.contains(id)
+                    );
+                }
+            }
+
+            for _i in 0..100 {
+                let id = fastrand::u64(0..315 * CHUNK_SIZE_BITS);
+                assert_eq!(
+                    read_state_value(&gzip_path, id).unwrap(),
+                    read_state_value(&zip_path, id).unwrap()
+                );
+            }
+
+            // Open data files answer many lookups, Roaring files from memory only.
+            let mut readers =
+                [&zip_path, &gzip_path, &roaring_path].map(|path| StateReader::open(path).unwrap());
+            std::fs::write(&roaring_path, []).unwrap();
+            assert!(read_state_value(&roaring_path, 0).is_err());
+            for id in states.iter().chain([0, u64::MAX - 1]) {
+                for reader in &mut readers {
+                    assert_eq!(reader.contains(id).unwrap(), states.contains(id));
+                }
+            }
+
+            // Many states can be looked up at once, in any order.
+            let mut ids: Vec<u64> = states
+                .iter()
+                .flat_map(|id| [id, id ^ 1, id.wrapping_add(CHUNK_SIZE_BITS)])
+                .collect();
+            fastrand::shuffle(&mut ids);
+            let expected: Vec<bool> = ids.iter().map(|&id| states.contains(id)).collect();
+            for reader in &mut readers {
+                assert_eq!(reader.contains_all(&ids).unwrap(), expected);
+                assert!(reader.contains_all(&[]).unwrap().is_empty());
+            }
+
+            // Random states are selected chunk by chunk, in the same way for all formats.
+            let mut rngs = readers.each_ref().map(|_| fastrand::Rng::with_seed(42));
+            let mut sampled_ids = std::collections::HashSet::new();
+            for _i in 0..100 {
+                let ids: Vec<u64> = readers
+                    .iter_mut()
+                    .zip(&mut rngs)
+                    .map(|(reader, rng)| reader.random_state(rng).unwrap().unwrap())
+                    .collect();
+                assert!(states.contains(ids[0]));
+                assert_eq!(ids[0], ids[1]);
+                assert!(states.contains(ids[2]));
+                sampled_ids.insert(ids[0]);
+            }
+            // Each chunk holds about 9 states and is likely to be selected.
+            assert!(sampled_ids.len() > 10);
+
+            // The chunks are regular gzip members which can be decompressed together.
+            let mut decompressed = Vec::new();
+            flate2::read::MultiGzDecoder::new(File::open(&gzip_path).unwrap())
+                .read_to_end(&mut decompressed)
+                .unwrap();
+            let expected_chunks_len: usize = [0, 1, 17, 314, u64::MAX / CHUNK_SIZE_BITS]
+                .iter()
+                .map(|&chunk_id| {
+                    let last_state_id = states
+                        .iter()
+                        .filter(|state_id| state_id / CHUNK_SIZE_BITS == chunk_id)
+                        .max()
+                        .unwrap();
+                    (last_state_id % CHUNK_SIZE_BITS / 8) as usize + 1
+                })
+                .sum();
+            assert_eq!(
+                decompressed.len(),
+                expected_chunks_len + 5 * GZIP_INDEX_ENTRY_SIZE
+            );
+        });
+    }
+
+    #[test]
+    fn states_empty_to_gzip() {
+        with_tempdir(|dir| {
+            let states_path = data_file_path(dir, "", "states");
+            let roaring_path = data_file_path(dir, "", "roaring");
+
+            write_states(
+                create_new_file(&states_path),
+                &states_path,
+                &roaring::RoaringTreemap::new(),
+                DataFormat::Gzip,
+            )
+            .unwrap();
+
+            assert!(!read_state_value(&states_path, 0).unwrap());
+            assert!(!read_state_value(&states_path, 1).unwrap());
+            assert!(!read_state_value(&states_path, u64::MAX).unwrap());
+            assert!(read_states(&states_path).unwrap().is_empty());
+            assert_eq!(read_chunks(&states_path).unwrap().count(), 0);
+
+            write_states(
+                create_new_file(&roaring_path),
+                &roaring_path,
+                &roaring::RoaringTreemap::new(),
+                DataFormat::Roaring,
+            )
+            .unwrap();
+            let mut file = File::open(&roaring_path).unwrap();
+            assert_eq!(
+                detect_format(&mut file, &roaring_path).unwrap(),
+                DataFormat::Roaring
+            );
+            assert!(!read_state_value(&roaring_path, 0).unwrap());
+            assert!(read_states(&roaring_path).unwrap().is_empty());
+
+            let mut rng = fastrand::Rng::new();
+            for path in [&states_path, &roaring_path] {
+                let mut reader = StateReader::open(path).unwrap();
+                assert_eq!(reader.random_state(&mut rng).unwrap(), None);
+            }
+        });
+    }
+
+    #[test]
+    fn data_errors() {
+        with_tempdir(|dir| {
+            let absent_path = data_file_path(dir, "", "absent");
+            let not_zip_path = data_file_path(dir, "", "not_zip");
+            let no_index_path = data_file_path(dir, "", "no_index");
+            let corrupted_path = data_file_path(dir, "", "corrupted");
+
+            assert!(matches!(
+                read_state_value(&absent_path, 0),
+                Err(DataError::IoError { .. })
+            ));
+
+            std::fs::write(&not_zip_path, b"Not a ZIP file").unwrap();
+            assert!(matches!(
+                read_state_value(&not_zip_path, 0),
+                Err(DataError::ZipError { .. })
+            ));
+
+            // Gzip file without the position of the index.
+            let mut file = create_new_file(&no_index_path);
+            GzBuilder::new()
+                .write(&mut file, Compression::default())
+                .finish()
+                .unwrap();
+            assert!(matches!(
+                read_state_value(&no_index_path, 0),
+                Err(DataError::CorruptHeader { .. })
+            ));
+
+            // ZIP file with corrupted chunk contents.
+            let states = roaring::RoaringTreemap::from_sorted_iter([CHUNK_SIZE_BITS - 1]).unwrap();
+            write_states(
+                create_new_file(&corrupted_path),
+                &corrupted_path,
+                &states,
+                DataFormat::Zip,
+            )
+            .unwrap();
+            let mut bytes = std::fs::read(&corrupted_path).unwrap();
+            let data_start = 30 + "chunk0".len();
+            bytes[data_start..data_start + 8].fill(0xFF);
+            std::fs::write(&corrupted_path, bytes).unwrap();
+            assert!(matches!(
+                read_state_value(&corrupted_path, CHUNK_SIZE_BITS - 1),
+                Err(DataError::ChunkReadFailed { chunk_id: 0, .. })
+            ));
+
+            let result = std::panic::catch_unwind(|| {
+                read_state_value_or_abort(&not_zip_path, 0);
+            });
+            assert!(result
+                .unwrap_err()
+                .downcast::<String>()
+                .unwrap()
+                .contains("not_zip"));
+        });
+    }
+
+    #[test]
+    fn chunks_size() {
+        assert_eq!(get_chunks_size(&roaring::RoaringTreemap::new()), (0, 0));
+
+        let states = roaring::RoaringTreemap::from_sorted_iter([
+            0,
+            7,
+            8,
+            CHUNK_SIZE_BITS - 1,
+            5 * CHUNK_SIZE_BITS + 100,
+            u64::MAX,
+        ])
+        .unwrap();
+
+        assert_eq!(
+            get_chunks_size(&states),
+            (3, CHUNK_SIZE_BYTES as u64 + 13 + CHUNK_SIZE_BYTES as u64)
+        );
+
+        with_tempdir(|dir| {
+            let states_path = data_file_path(dir, "", "states");
+
+            write_states(
+                create_new_file(&states_path),
+                &states_path,
+                &states,
+                DataFormat::Zip,
+            )
+            .unwrap();
+
+            let mut zip = zip::ZipArchive::new(File::open(&states_path).unwrap()).unwrap();
+            let zip_chunks_size: u64 = (0..zip.len())
+                .map(|i| zip.by_index(i).unwrap().size())
+                .sum();
+            assert_eq!(
+                get_chunks_size(&states),
+                (zip.len() as u64, zip_chunks_size)
+            );
         });
     }
 
@@ -285,10 +1358,18 @@ pub mod tests {
             states.insert((chunk_id + 1) * CHUNK_SIZE_BITS - 1);
         }
 
-        run_in_tempdir(|| {
-            write_states("states", &states);
+        with_tempdir(|dir| {
+            let states_path = data_file_path(dir, "", "states");
 
-            let file = File::open("states").unwrap();
+            write_states(
+                create_new_file(&states_path),
+                &states_path,
+                &states,
+                DataFormat::Zip,
+            )
+            .unwrap();
+
+            let file = File::open(&states_path).unwrap();
             let file_len = file.metadata().unwrap().len();
 
             assert!(file_len * 800 < (314 * CHUNK_SIZE_BYTES) as u64);
@@ -302,11 +1383,14 @@ pub mod tests {
 
     #[test]
     fn mistake_protection() {
-        run_in_tempdir(|| {
-            File::create("exists.txt").unwrap();
+        with_tempdir(|dir| {
+            let exists_path = data_file_path(dir, "", "exists.txt");
+            let absent_path = data_file_path(dir, "", "absent.txt");
+
+            File::create(&exists_path).unwrap();
 
             let result = std::panic::catch_unwind(|| {
-                abort_if_path_exists("exists.txt");
+                create_new_file(&exists_path);
             });
             assert!(result.is_err());
             assert!(result
@@ -316,9 +1400,10 @@ pub mod tests {
                 .contains("exists.txt"));
 
             let result = std::panic::catch_unwind(|| {
-                abort_if_path_exists("absent.txt");
+                create_new_file(&absent_path);
             });
             assert!(result.is_ok());
+            assert!(std::path::Path::new(&absent_path).exists());
         });
     }
 }
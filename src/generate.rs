@@ -1,85 +1,455 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
 use std::io::{self, Write};
+use std::panic;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
 use roaring::RoaringTreemap;
 
-use crate::board_state::BoardState;
-use crate::file_operations;
+use crate::board_state::{BoardState, Ruleset};
+use crate::file_operations::{self, DataFormat};
+
+/// Amount of information printed while generating data files
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Nothing is printed (errors still terminate the thread)
+    Quiet,
+
+    /// Progress and number of states are printed
+    Normal,
+
+    /// Elapsed time is also printed for each step
+    Verbose,
+
+    /// Peak memory usage is also printed after each step (only on Linux)
+    Profile,
+}
 
 /// Generate data files needed to play a game
 ///
 /// Generate one data file with winning states per player and one file with all explored states.
-pub fn generate(init_states: &[BoardState]) {
-    // Make sure the data files do not already exist.
-    check_before_generate();
+/// All data files are written in the given `format`.
+pub fn generate(init_states: &[BoardState], format: DataFormat, verbosity: Verbosity) {
+    generate_with_prefix(init_states, format, verbosity, "", "", None, true);
+}
+
+/// Same as `generate`, but the data files are written to `directory` and their names start with `prefix` followed by an underscore
+///
+/// An empty `directory` is the current directory.
+/// If provided, `expected_states` (the number of reachable states printed by `count`) is used to print the progress
+/// of the exploration and its remaining time. Without `overwrite_guard`, existing files are overwritten instead of
+/// stopping the generation (see `file_operations::overwrite_file`).
+pub fn generate_with_prefix(
+    init_states: &[BoardState],
+    format: DataFormat,
+    verbosity: Verbosity,
+    directory: impl AsRef<Path>,
+    prefix: &str,
+    expected_states: Option<u64>,
+    overwrite_guard: bool,
+) {
+    let start_instant = Instant::now();
+    let print_elapsed = |step: &str, step_instant: Instant| {
+        if verbosity >= Verbosity::Verbose {
+            println!("{} took {:.1?}.", step, step_instant.elapsed());
+        }
+        if verbosity >= Verbosity::Profile {
+            println!("{}", format_peak_memory_usage(peak_memory_usage()));
+        }
+    };
+
+    let ruleset = common_ruleset(init_states);
+
+    let get_path = |path| file_operations::data_file_path(&directory, prefix, path);
+    let all_states_path = get_path(file_operations::ALL_STATES_PATH);
+    let winning_states_path = file_operations::WINNING_STATES_PATH.map(get_path);
+
+    // Create the data files now, so that nothing is generated if one of them already exists.
+    let (all_states_file, [player_0_winning_states_file, player_1_winning_states_file]) =
+        create_data_files(&all_states_path, &winning_states_path, overwrite_guard);
+
+    // If generation fails, the data files are removed so that they don't prevent the next one.
+    // Files truncated without the guard are kept, since they may be managed by the caller (e.g. named pipes).
+    let created_paths = [
+        all_states_path.as_str(),
+        &winning_states_path[0],
+        &winning_states_path[1],
+    ];
+    let removed_paths: &[&str] = if overwrite_guard { &created_paths } else { &[] };
+    remove_files_on_panic(removed_paths, || {
+        if verbosity >= Verbosity::Normal {
+            println!("Generating states. This will take a while.");
+        }
+
+        let step_instant = Instant::now();
+        let mut progress_opt =
+            (verbosity >= Verbosity::Normal).then(|| ExplorationProgress::new(expected_states));
+        let mut remaining_states =
+            collect_reachable_states_reporting(init_states, &mut progress_opt);
+        print_elapsed("Exploration", step_instant);
+
+        // Save all states seen during exploration.
+        file_operations::write_states_or_abort(
+            all_states_file,
+            &all_states_path,
+            &remaining_states,
+            format,
+        );
+        if verbosity >= Verbosity::Normal {
+            println!("{} explored states saved.", remaining_states.len());
+        }
 
-    println!("Generating states. This will take a while.");
+        let step_instant = Instant::now();
+        let player_0_winning_states =
+            collect_winning_states(&mut remaining_states, ruleset, verbosity);
+        print_elapsed("Search for winning states", step_instant);
+
+        // Save winning states for player 0.
+        file_operations::write_states_or_abort(
+            player_0_winning_states_file,
+            &winning_states_path[0],
+            &player_0_winning_states,
+            format,
+        );
+        if verbosity >= Verbosity::Normal {
+            println!(
+                "{} winning states saved for player 0.",
+                player_0_winning_states.len()
+            );
+        }
+
+        remaining_states |= player_0_winning_states;
+        let player_1_winning_states = collect_reachable_states(init_states) - remaining_states;
+
+        // Save winning states for player 1.
+        file_operations::write_states_or_abort(
+            player_1_winning_states_file,
+            &winning_states_path[1],
+            &player_1_winning_states,
+            format,
+        );
+        if verbosity >= Verbosity::Normal {
+            println!(
+                "{} winning states saved for player 1.",
+                player_1_winning_states.len()
+            );
+        }
+    });
 
-    let mut remaining_states = collect_reachable_states(init_states);
+    print_elapsed("Generation", start_instant);
+}
 
-    // Save all states seen during exploration.
-    file_operations::write_states(file_operations::ALL_STATES_PATH, &remaining_states);
-    println!("{} explored states saved.", remaining_states.len());
+/// States reachable from initial states, partitioned by outcome
+#[derive(Clone, Debug, PartialEq)]
+pub struct SolveResult {
+    /// All reachable states
+    pub all_states: RoaringTreemap,
 
-    let player_0_winning_states = collect_winning_states(&mut remaining_states);
+    /// Winning states of each player
+    pub winning_states: [RoaringTreemap; 2],
 
-    // Save winning states for player 0.
-    file_operations::write_states(
-        file_operations::WINNING_STATES_PATH[0],
-        &player_0_winning_states,
-    );
-    println!(
-        "{} winning states saved for player 0.",
-        player_0_winning_states.len()
-    );
+    /// States from which neither player can guarantee a win
+    pub drawn_states: RoaringTreemap,
+}
 
-    remaining_states |= player_0_winning_states;
-    let player_1_winning_states = collect_reachable_states(init_states) - remaining_states;
+/// Return all states reachable from `init_states`, with the winning states of each player and the drawn states
+///
+/// Nothing is written or printed. Unlike `generate`, which frees each set once saved, all sets are kept in memory
+/// at the same time, so this is meant for subtrees.
+pub fn solve_subtree(init_states: &[BoardState]) -> SolveResult {
+    let ruleset = common_ruleset(init_states);
+
+    let all_states = collect_reachable_states(init_states);
+    let mut drawn_states = all_states.clone();
+    let player_0_winning_states =
+        collect_winning_states(&mut drawn_states, ruleset, Verbosity::Quiet);
+    let player_1_winning_states = &all_states - &drawn_states - &player_0_winning_states;
+
+    SolveResult {
+        all_states,
+        winning_states: [player_0_winning_states, player_1_winning_states],
+        drawn_states,
+    }
+}
 
-    // Save winning states for player 1.
-    file_operations::write_states(
-        file_operations::WINNING_STATES_PATH[1],
-        &player_1_winning_states,
+/// Return the rules followed by all `init_states`, which all explored states follow too
+///
+/// Panic if the initial states follow different rules.
+fn common_ruleset(init_states: &[BoardState]) -> &'static Ruleset {
+    let ruleset = init_states
+        .first()
+        .map_or(&Ruleset::OFFICIAL, BoardState::get_ruleset);
+    assert!(
+        init_states.iter().all(|s| s.get_ruleset() == ruleset),
+        "All initial states should follow the same rules"
     );
+
+    ruleset
+}
+
+/// Print the number of states reachable from `init_states`, without generating any data file
+///
+/// This also prints the number of chunks and bytes (before compression) in a data file containing all those states.
+pub fn count(init_states: &[BoardState]) {
+    println!("Counting states. This will take a while.");
+
+    let reachable_states = collect_reachable_states(init_states);
+    let (chunks_count, chunks_size) = file_operations::get_chunks_size(&reachable_states);
+
+    println!("{} reachable states.", reachable_states.len());
     println!(
-        "{} winning states saved for player 1.",
-        player_1_winning_states.len()
+        "{} chunks and {} bytes before compression for all states.",
+        chunks_count, chunks_size
     );
 }
 
 /// Return all states reachable from at least one of the `init_states`
-fn collect_reachable_states(init_states: &[BoardState]) -> RoaringTreemap {
+pub fn collect_reachable_states(init_states: &[BoardState]) -> RoaringTreemap {
+    collect_reachable_states_reporting(init_states, &mut None)
+}
+
+/// Return the number of states reachable from at least one of the `init_states`
+///
+/// Visited states still have to be stored during the exploration, but they are dropped before returning.
+pub fn count_reachable_states(init_states: &[BoardState]) -> u64 {
+    collect_reachable_states(init_states).len()
+}
+
+/// Same as `collect_reachable_states`, but report the progress of the exploration to `progress_opt` if provided
+fn collect_reachable_states_reporting(
+    init_states: &[BoardState],
+    progress_opt: &mut Option<ExplorationProgress>,
+) -> RoaringTreemap {
     let mut reachable_states = RoaringTreemap::new();
 
     for state in init_states {
         // Mark all explored states.
-        collect_reachable_states_recursively(state.clone(), &mut reachable_states);
+        collect_reachable_states_recursively(state.clone(), &mut reachable_states, progress_opt);
     }
 
     reachable_states
 }
 
+// Number of explored states between two progress reports.
+const PROGRESS_INTERVAL: u64 = 1 << 24;
+
+// Number of progress reports over which the exploration speed is measured.
+const PROGRESS_WINDOW: usize = 8;
+
+/// Progress of the exploration of reachable states, printed at regular intervals
+struct ExplorationProgress {
+    expected_states: Option<u64>,
+    explored_states: u64,
+
+    // Instant and number of explored states of the last reports, oldest first.
+    window: VecDeque<(Instant, u64)>,
+}
+
+impl ExplorationProgress {
+    fn new(expected_states: Option<u64>) -> Self {
+        ExplorationProgress {
+            expected_states,
+            explored_states: 0,
+            window: VecDeque::from([(Instant::now(), 0)]),
+        }
+    }
+
+    /// Count a newly explored state, and print the progress if a report is due
+    fn record_state(&mut self) {
+        self.explored_states += 1;
+        if !self.explored_states.is_multiple_of(PROGRESS_INTERVAL) {
+            return;
+        }
+
+        self.window
+            .push_back((Instant::now(), self.explored_states));
+        if self.window.len() > PROGRESS_WINDOW + 1 {
+            self.window.pop_front();
+        }
+
+        let (first_instant, first_explored_states) = self.window[0];
+        let states_per_second = (self.explored_states - first_explored_states) as f64
+            / first_instant.elapsed().as_secs_f64();
+
+        println!(
+            "{}",
+            format_progress(
+                self.explored_states,
+                self.expected_states,
+                states_per_second
+            )
+        );
+    }
+}
+
+/// Return the largest amount of memory used by the process so far (its peak resident set size), in bytes
+///
+/// Return `None` if it is unknown, e.g. on platforms other than Linux.
+fn peak_memory_usage() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+
+    // The value is given in kB, e.g. `VmHWM:     1234 kB`.
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// Return a line describing the peak memory usage `bytes_opt`
+fn format_peak_memory_usage(bytes_opt: Option<u64>) -> String {
+    match bytes_opt {
+        Some(bytes) => format!(
+            "Peak memory usage so far : {:.1} MiB.",
+            bytes as f64 / (1024 * 1024) as f64
+        ),
+        None => "Peak memory usage unavailable on this platform.".to_string(),
+    }
+}
+
+/// Return a line describing the progress of an exploration, with the remaining time if `expected_states_opt` is provided
+fn format_progress(
+    explored_states: u64,
+    expected_states_opt: Option<u64>,
+    states_per_second: f64,
+) -> String {
+    let mut line = format!(
+        "{} states explored ({:.0} states/s",
+        explored_states, states_per_second
+    );
+
+    if let Some(expected_states) = expected_states_opt {
+        let remaining_states = expected_states.saturating_sub(explored_states);
+        let remaining_time = Duration::from_secs_f64(remaining_states as f64 / states_per_second);
+        line += &format!(
+            ", {:.1}% done, about {} left",
+            explored_states as f64 * 100.0 / expected_states as f64,
+            format_duration(remaining_time)
+        );
+    }
+
+    line + ")."
+}
+
+/// Return `duration` in hours, minutes and seconds, e.g. `1h02m03s`
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+
+    format!(
+        "{}h{:02}m{:02}s",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Return the states of `within` following `ruleset` from which the next player can win with a single move
+pub fn winning_in_one(within: &RoaringTreemap, ruleset: &'static Ruleset) -> RoaringTreemap {
+    within
+        .iter()
+        .filter(|&id| BoardState::from_id_with_ruleset(id, ruleset).can_win_in_one())
+        .collect()
+}
+
+/// Return the states of `within` following `ruleset` which can only be reached from other states of `within` by a move sending a piece back
+///
+/// States which can't be reached from any state of `within`, such as initial states, are not returned.
+pub fn reachable_only_by_bump(
+    within: &RoaringTreemap,
+    ruleset: &'static Ruleset,
+) -> RoaringTreemap {
+    let mut reached_by_bump = RoaringTreemap::new();
+    let mut reached_without_bump = RoaringTreemap::new();
+
+    for id in within {
+        let state = BoardState::from_id_with_ruleset(id, ruleset);
+        if state.is_ended() {
+            continue;
+        }
+
+        for piece in state.movable_labels() {
+            let (next_state, collision) = state
+                .get_next_state_and_collision(piece)
+                .expect("Movable pieces have a next state");
+
+            if collision {
+                reached_by_bump.insert(next_state.get_id());
+            } else {
+                reached_without_bump.insert(next_state.get_id());
+            }
+        }
+    }
+
+    (reached_by_bump - reached_without_bump) & within
+}
+
+/// Return the minimum number of moves needed to reach each state of `within` from `root`
+///
+/// States of `within` which can't be reached from `root` (through states of `within`) are absent from the returned map.
+pub fn depth_from(root: &BoardState, within: &RoaringTreemap) -> HashMap<u64, u32> {
+    let mut depths = HashMap::new();
+    if !within.contains(root.get_id()) {
+        return depths;
+    }
+
+    // Explore states in breadth-first order, so that each state is first seen at its minimum depth.
+    let mut queue = VecDeque::from([(root.clone(), 0)]);
+    depths.insert(root.get_id(), 0);
+
+    while let Some((state, depth)) = queue.pop_front() {
+        if state.is_ended() {
+            continue;
+        }
+
+        for next_state in state.get_next_states() {
+            let next_state_id = next_state.get_id();
+
+            if within.contains(next_state_id) && !depths.contains_key(&next_state_id) {
+                depths.insert(next_state_id, depth + 1);
+                queue.push_back((next_state, depth + 1));
+            }
+        }
+    }
+
+    depths
+}
+
 /// Recursively (depth-first order) mark states reachable from `current_state`
 #[decurse::decurse_unsound]
 fn collect_reachable_states_recursively(
     current_state: BoardState,
     reachable_states: &mut RoaringTreemap,
+    progress_opt: &mut Option<ExplorationProgress>,
 ) {
     // Note: `insert` returns `false` if `current_state.get_id()` is already in `reachable_states`.
-    if !reachable_states.insert(current_state.get_id()) || current_state.is_ended() {
+    if !reachable_states.insert(current_state.get_id()) {
+        return;
+    }
+
+    if let Some(progress) = progress_opt {
+        progress.record_state();
+    }
+
+    if current_state.is_ended() {
         return;
     }
 
     for next_state in current_state.get_next_states() {
         // Explore recursively.
-        collect_reachable_states_recursively(next_state, reachable_states);
+        collect_reachable_states_recursively(next_state, reachable_states, progress_opt);
     }
 }
 
 /// Return all winning states of player 0
 ///
-/// Initially, `remaining_states` must contain all reachable states.
+/// Initially, `remaining_states` must contain all reachable states following `ruleset`.
 /// After calling this function, `remaining_states` will contain the states for which neither player can guarantee a win.
-fn collect_winning_states(remaining_states: &mut RoaringTreemap) -> RoaringTreemap {
+/// Each iteration is reported according to `verbosity`. Iterations don't match distances to the end of the game :
+/// the first one already finds most winning states, at any distance, and later ones only resolve loops (see `depths_to_win`).
+fn collect_winning_states(
+    remaining_states: &mut RoaringTreemap,
+    ruleset: &'static Ruleset,
+    verbosity: Verbosity,
+) -> RoaringTreemap {
     let mut player_0_winning_states = RoaringTreemap::new();
 
     let mut previous_remaining_states_len: u64 = remaining_states.len();
@@ -87,21 +457,37 @@ fn collect_winning_states(remaining_states: &mut RoaringTreemap) -> RoaringTreem
 
     // Explore `remaining_states` several times until no new winning state can be found.
     for iteration in 1.. {
-        print!("Iteration {} ... ", iteration);
-        // Without flushing, nothing is printed until the next newline.
-        io::stdout().flush().expect("stdout should be writable");
+        let iteration_instant = Instant::now();
+        if verbosity >= Verbosity::Normal {
+            print!("Iteration {} ... ", iteration);
+            // Without flushing, nothing is printed until the next newline.
+            io::stdout().flush().expect("stdout should be writable");
+        }
 
-        collect_winning_states_scan_remaining(remaining_states, &mut player_0_winning_states);
+        collect_winning_states_scan_remaining(
+            remaining_states,
+            &mut player_0_winning_states,
+            ruleset,
+        );
 
         let remaining_states_diff = previous_remaining_states_len - remaining_states.len();
         let player_0_winning_states_diff =
             player_0_winning_states.len() - previous_player_0_winning_states_len;
 
-        println!(
-            "Found {} new winning states for player 0 and {} for player 1.",
-            player_0_winning_states_diff,
-            remaining_states_diff - player_0_winning_states_diff
-        );
+        if verbosity >= Verbosity::Normal {
+            print!(
+                "Found {} new winning states for player 0 and {} for player 1.",
+                player_0_winning_states_diff,
+                remaining_states_diff - player_0_winning_states_diff
+            );
+            if verbosity >= Verbosity::Verbose {
+                print!(" ({:.1?})", iteration_instant.elapsed());
+            }
+            println!();
+            if verbosity >= Verbosity::Profile {
+                println!("{}", format_peak_memory_usage(peak_memory_usage()));
+            }
+        }
 
         if remaining_states_diff == 0 {
             break;
@@ -120,6 +506,7 @@ fn collect_winning_states(remaining_states: &mut RoaringTreemap) -> RoaringTreem
 fn collect_winning_states_scan_remaining(
     remaining_states: &mut RoaringTreemap,
     player_0_winning_states: &mut RoaringTreemap,
+    ruleset: &'static Ruleset,
 ) {
     // From here until the clean up, if a state ID is in `remaining_states` AND in `seen_or_player_0_winning_states`,
     // then the corresponding state has been seen but was not found winning in the current iteration.
@@ -128,7 +515,7 @@ fn collect_winning_states_scan_remaining(
     let mut next_state_id_from = 0;
     while let Some(state_id) = treemap_next_value(remaining_states, next_state_id_from) {
         collect_winning_states_recursively(
-            BoardState::from(state_id),
+            BoardState::from_id_with_ruleset(state_id, ruleset),
             remaining_states,
             seen_or_player_0_winning_states,
         );
@@ -218,13 +605,66 @@ fn collect_winning_states_recursively(
     current_eval
 }
 
-/// Terminate thread if `generate` would write to a file that already exists
-fn check_before_generate() {
-    file_operations::abort_if_path_exists(file_operations::ALL_STATES_PATH);
+/// Create the data files written by `generate`, terminating thread if one of them already exists
+///
+/// Return the file with all states at `all_states_path`, followed by the winning states files of both players.
+/// On termination, the files created so far are removed again.
+/// Without `overwrite_guard`, existing files are truncated instead, and never removed.
+fn create_data_files(
+    all_states_path: &str,
+    winning_states_path: &[String; 2],
+    overwrite_guard: bool,
+) -> (File, [File; 2]) {
+    let paths = [
+        all_states_path,
+        &winning_states_path[0],
+        &winning_states_path[1],
+    ];
+    if !overwrite_guard {
+        let [all_states_file, player_0_winning_states_file, player_1_winning_states_file] =
+            paths.map(file_operations::overwrite_file);
+        return (
+            all_states_file,
+            [player_0_winning_states_file, player_1_winning_states_file],
+        );
+    }
+
+    let mut files = Vec::with_capacity(paths.len());
+    for path in &paths {
+        match panic::catch_unwind(|| file_operations::create_new_file(path)) {
+            Ok(file) => files.push(file),
+            Err(e) => {
+                let created_files_count = files.len();
+                drop(files);
+
+                for created_path in &paths[..created_files_count] {
+                    // Ignore errors since the original failure is the one worth reporting.
+                    let _ = fs::remove_file(created_path);
+                }
 
-    for player in 0..=1 {
-        file_operations::abort_if_path_exists(file_operations::WINNING_STATES_PATH[player]);
+                panic::resume_unwind(e);
+            }
+        }
     }
+
+    let [all_states_file, player_0_winning_states_file, player_1_winning_states_file]: [File; 3] =
+        files.try_into().expect("All data files should be created");
+    (
+        all_states_file,
+        [player_0_winning_states_file, player_1_winning_states_file],
+    )
+}
+
+/// Run `f` and return its result, removing the files at `paths` if it terminates thread
+fn remove_files_on_panic<T>(paths: &[&str], f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(panic::AssertUnwindSafe(f)).unwrap_or_else(|e| {
+        for path in paths {
+            // Ignore errors since the original failure is the one worth reporting.
+            let _ = fs::remove_file(path);
+        }
+
+        panic::resume_unwind(e)
+    })
 }
 
 /// Get the next value in `treemap`, starting from (and including) `from`
@@ -247,19 +687,35 @@ fn treemap_next_value(treemap: &RoaringTreemap, from: u64) -> Option<u64> {
 }
 
 #[cfg(test)]
-mod tests {
-    use std::fs::File;
+pub mod tests {
     use std::slice;
 
     use super::*;
 
+    /// Generate the data files of the states reachable from `init_states` in `directory`
+    pub fn generate_in(directory: &Path, init_states: &[BoardState]) {
+        generate_with_prefix(
+            init_states,
+            DataFormat::Zip,
+            Verbosity::Normal,
+            directory,
+            "",
+            None,
+            true,
+        );
+    }
+
     #[test]
     fn data_generation() {
         let init_state = BoardState::from(85065666045);
 
         let get_generate_result = || {
             std::panic::catch_unwind(|| {
-                generate(slice::from_ref(&init_state));
+                generate(
+                    slice::from_ref(&init_state),
+                    DataFormat::Zip,
+                    Verbosity::Verbose,
+                );
             })
         };
 
@@ -269,7 +725,7 @@ mod tests {
                 Some(player) => file_operations::WINNING_STATES_PATH[player],
             };
 
-            file_operations::read_state_value(path, id)
+            file_operations::read_state_value(path, id).unwrap()
         };
 
         file_operations::tests::run_in_tempdir(|| {
@@ -342,7 +798,11 @@ mod tests {
 
         let get_generate_result = || {
             std::panic::catch_unwind(|| {
-                generate(slice::from_ref(&init_state));
+                generate(
+                    slice::from_ref(&init_state),
+                    DataFormat::Gzip,
+                    Verbosity::Quiet,
+                );
             })
         };
 
@@ -352,7 +812,7 @@ mod tests {
                 Some(player) => file_operations::WINNING_STATES_PATH[player],
             };
 
-            file_operations::read_state_value(path, id)
+            file_operations::read_state_value(path, id).unwrap()
         };
 
         file_operations::tests::run_in_tempdir(|| {
@@ -443,6 +903,151 @@ mod tests {
         });
     }
 
+    #[test]
+    fn count_without_writing() {
+        file_operations::tests::run_in_tempdir(|| {
+            count(&[BoardState::from(85065666045)]);
+
+            assert_eq!(fs::read_dir(".").unwrap().count(), 0);
+        });
+
+        let init_states = [5057791486, 85065666045].map(BoardState::from);
+        assert_eq!(
+            count_reachable_states(&init_states),
+            collect_reachable_states(&init_states).len()
+        );
+        assert_eq!(
+            count_reachable_states(&init_states[1..]),
+            1 + init_states[1]
+                .get_next_states()
+                .map(|s| collect_reachable_states(slice::from_ref(&s)))
+                .fold(RoaringTreemap::new(), |a, b| a | b)
+                .len()
+        );
+        assert_eq!(count_reachable_states(&[]), 0);
+    }
+
+    #[test]
+    fn subtree_solving() {
+        let init_states = [5057791486, 85065666045].map(BoardState::from);
+        let result = solve_subtree(&init_states);
+
+        assert_eq!(result.all_states, collect_reachable_states(&init_states));
+        assert!(!result.drawn_states.is_empty());
+        assert_eq!(
+            &result.winning_states[0] | &result.winning_states[1] | &result.drawn_states,
+            result.all_states
+        );
+        assert!(result.winning_states[0].is_disjoint(&result.winning_states[1]));
+        assert!(result.drawn_states.is_disjoint(&result.winning_states[0]));
+        assert!(result.drawn_states.is_disjoint(&result.winning_states[1]));
+
+        // The partition is the one saved by `generate`.
+        file_operations::tests::with_tempdir(|dir| {
+            tests::generate_in(dir, &init_states);
+            let read = |path| {
+                file_operations::read_states(&file_operations::data_file_path(dir, "", path))
+                    .unwrap()
+            };
+
+            assert_eq!(read(file_operations::ALL_STATES_PATH), result.all_states);
+            for player in 0..=1 {
+                assert_eq!(
+                    read(file_operations::WINNING_STATES_PATH[player]),
+                    result.winning_states[player]
+                );
+            }
+        });
+
+        assert_eq!(solve_subtree(&[]).all_states.len(), 0);
+    }
+
+    #[test]
+    fn won_in_one() {
+        let init_state = BoardState::from(85065666045);
+        let seen_states = collect_reachable_states(slice::from_ref(&init_state));
+
+        let puzzles = winning_in_one(&seen_states, &Ruleset::OFFICIAL);
+        assert!(!puzzles.is_empty());
+        assert!(puzzles.is_subset(&seen_states));
+
+        for id in seen_states.iter() {
+            let state = BoardState::from(id);
+            let winning_move_exists = !state.is_ended()
+                && state.get_next_states().any(|next_state| {
+                    next_state.is_ended()
+                        && 1 - next_state.get_next_player() == state.get_next_player()
+                });
+            assert_eq!(puzzles.contains(id), winning_move_exists);
+        }
+
+        assert!(winning_in_one(&RoaringTreemap::new(), &Ruleset::OFFICIAL).is_empty());
+    }
+
+    #[test]
+    fn bump_only_states() {
+        let init_state = BoardState::from(85065666045);
+        let seen_states = collect_reachable_states(slice::from_ref(&init_state));
+
+        let bump_only_states = reachable_only_by_bump(&seen_states, &Ruleset::OFFICIAL);
+        assert!(!bump_only_states.is_empty());
+        assert!(bump_only_states.is_subset(&seen_states));
+        assert!(!bump_only_states.contains(init_state.get_id()));
+
+        // Left piece 0 sends top piece 0 back, so the next state is only reached by a bump.
+        let state = BoardState::from_positions_unchecked(1, [[1, 0, 0, 0, 0], [0, 1, 0, 0, 0]]);
+        let (next_state, collision) = state.get_next_state_and_collision(0).unwrap();
+        assert!(collision);
+        let within = RoaringTreemap::from_iter([state.get_id(), next_state.get_id()]);
+        assert_eq!(
+            reachable_only_by_bump(&within, &Ruleset::OFFICIAL),
+            RoaringTreemap::from_iter([next_state.get_id()])
+        );
+
+        // The same state is also reached by moving left piece 1, without collision.
+        let other_state = BoardState::from_positions_unchecked(1, [[0; 5], [2, 0, 0, 0, 0]]);
+        let (other_next_state, collision) = other_state.get_next_state_and_collision(1).unwrap();
+        assert!(!collision);
+        assert_eq!(other_next_state.get_id(), next_state.get_id());
+        let within =
+            RoaringTreemap::from_iter([state.get_id(), other_state.get_id(), next_state.get_id()]);
+        assert!(reachable_only_by_bump(&within, &Ruleset::OFFICIAL).is_empty());
+    }
+
+    #[test]
+    fn depths() {
+        let init_state = BoardState::from(85065666045);
+        let seen_states = collect_reachable_states(slice::from_ref(&init_state));
+
+        let depths = depth_from(&init_state, &seen_states);
+        assert_eq!(depths.len() as u64, seen_states.len());
+        assert_eq!(depths[&init_state.get_id()], 0);
+
+        for (&id, &depth) in &depths {
+            let state = BoardState::from(id);
+            if !state.is_ended() {
+                for next_state in state.get_next_states() {
+                    assert!(depths[&next_state.get_id()] <= depth + 1);
+                }
+            }
+        }
+
+        let second_state = init_state.get_next_state(4).unwrap();
+        assert_eq!(depths[&second_state.get_id()], 1);
+        assert_eq!(depths[&second_state.get_next_state(0).unwrap().get_id()], 2);
+
+        // Depths are restricted to the states of `within`.
+        let mut within = seen_states.clone();
+        within.remove(second_state.get_id());
+        let restricted_depths = depth_from(&init_state, &within);
+        assert!(!restricted_depths.contains_key(&second_state.get_id()));
+        for (id, depth) in restricted_depths {
+            assert!(depth >= depths[&id]);
+        }
+
+        assert!(depth_from(&init_state, &RoaringTreemap::new()).is_empty());
+    }
+
     #[test]
     fn simple_endgame_exploration() {
         let init_state = BoardState::from(100382226046);
@@ -450,7 +1055,8 @@ mod tests {
         let seen_states = collect_reachable_states(slice::from_ref(&init_state));
 
         let mut remaining_states = seen_states.clone();
-        let mut winning_states = collect_winning_states(&mut remaining_states);
+        let mut winning_states =
+            collect_winning_states(&mut remaining_states, &Ruleset::OFFICIAL, Verbosity::Normal);
 
         let init_state_is_winning = winning_states.contains(init_state.get_id());
 
@@ -480,7 +1086,11 @@ mod tests {
             let seen_states = collect_reachable_states(slice::from_ref(&init_state));
 
             let mut remaining_states = seen_states.clone();
-            let mut winning_states = collect_winning_states(&mut remaining_states);
+            let mut winning_states = collect_winning_states(
+                &mut remaining_states,
+                &Ruleset::OFFICIAL,
+                Verbosity::Normal,
+            );
 
             if player == 1 {
                 winning_states = &seen_states - (remaining_states | winning_states);
@@ -519,7 +1129,11 @@ mod tests {
             let seen_states = collect_reachable_states(slice::from_ref(&init_state));
 
             let mut remaining_states = seen_states.clone();
-            let mut winning_states = collect_winning_states(&mut remaining_states);
+            let mut winning_states = collect_winning_states(
+                &mut remaining_states,
+                &Ruleset::OFFICIAL,
+                Verbosity::Normal,
+            );
 
             if player == 1 {
                 winning_states = &seen_states - (remaining_states | winning_states);
@@ -571,21 +1185,66 @@ mod tests {
     }
 
     #[test]
-    fn mistake_protection() {
-        let get_check_result = || {
-            std::panic::catch_unwind(|| {
-                check_before_generate();
-            })
-        };
+    fn exploration_progress() {
+        assert_eq!(
+            format_progress(2_000_000, None, 1_000_000.4),
+            "2000000 states explored (1000000 states/s)."
+        );
+        assert_eq!(
+            format_progress(2_000_000, Some(8_000_000), 1_000.0),
+            "2000000 states explored (1000 states/s, 25.0% done, about 1h40m00s left)."
+        );
+
+        // More states than expected leave no remaining time.
+        assert!(format_progress(9, Some(8), 1.0).ends_with("112.5% done, about 0h00m00s left)."));
+
+        assert_eq!(format_duration(Duration::from_secs(3723)), "1h02m03s");
+        assert_eq!(format_duration(Duration::from_millis(59_999)), "0h00m59s");
+    }
+
+    #[test]
+    fn memory_usage() {
+        assert_eq!(
+            format_peak_memory_usage(Some(3 * 1024 * 1024 / 2)),
+            "Peak memory usage so far : 1.5 MiB."
+        );
+        assert!(format_peak_memory_usage(None).contains("unavailable"));
 
+        if cfg!(target_os = "linux") {
+            let bytes = peak_memory_usage().unwrap();
+            let buffer = std::hint::black_box(vec![1u8; 64 * 1024 * 1024]);
+            assert!(peak_memory_usage().unwrap() >= bytes);
+            assert!(peak_memory_usage().unwrap() >= buffer.len() as u64);
+        }
+    }
+
+    #[test]
+    fn mistake_protection() {
         for path in [file_operations::ALL_STATES_PATH]
             .iter()
             .chain(file_operations::WINNING_STATES_PATH.iter())
         {
-            file_operations::tests::run_in_tempdir(|| {
+            file_operations::tests::with_tempdir(|dir| {
+                let get_path = |p| file_operations::data_file_path(dir, "", p);
+                let all_paths = get_path(file_operations::ALL_STATES_PATH);
+                let winning_paths = file_operations::WINNING_STATES_PATH.map(get_path);
+                let all_paths = [
+                    all_paths,
+                    winning_paths[0].clone(),
+                    winning_paths[1].clone(),
+                ];
+                let get_check_result = || {
+                    std::panic::catch_unwind(|| {
+                        create_data_files(&all_paths[0], &winning_paths, true);
+                    })
+                };
+
                 assert!(get_check_result().is_ok());
+                for p in &all_paths {
+                    fs::remove_file(p).unwrap();
+                }
 
-                File::create(path).unwrap();
+                File::create(get_path(path)).unwrap();
 
                 let result = get_check_result();
                 assert!(result.is_err());
@@ -594,6 +1253,30 @@ mod tests {
                     .downcast::<String>()
                     .unwrap()
                     .contains(path));
+
+                // Only the file created beforehand should remain.
+                for p in &all_paths {
+                    assert_eq!(Path::new(p).exists(), *p == get_path(path));
+                }
+
+                // Without the guard, existing files are truncated.
+                fs::write(get_path(path), b"data").unwrap();
+                let (all_states_file, winning_states_files) =
+                    create_data_files(&all_paths[0], &winning_paths, false);
+                drop((all_states_file, winning_states_files));
+                for p in &all_paths {
+                    assert_eq!(fs::metadata(p).unwrap().len(), 0);
+                }
+
+                // Data files are removed if generation fails after creating them.
+                let paths = all_paths.each_ref().map(String::as_str);
+                assert_eq!(remove_files_on_panic(&paths, || 3), 3);
+                assert!(all_paths.iter().all(|p| Path::new(p).exists()));
+                let result = std::panic::catch_unwind(|| {
+                    remove_files_on_panic(&paths, || panic!("Generation failed"))
+                });
+                assert!(result.is_err());
+                assert!(all_paths.iter().all(|p| !Path::new(p).exists()));
             });
         }
     }
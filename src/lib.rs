@@ -0,0 +1,9 @@
+//! Solver for the Squadro board game
+
+pub mod analysis;
+pub mod board_state;
+pub mod database;
+pub mod file_operations;
+pub mod generate;
+pub mod play;
+pub mod session;
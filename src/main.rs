@@ -1,22 +1,83 @@
-mod board_state;
-mod file_operations;
-mod generate;
-mod play;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::Duration;
 
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::builder::TypedValueParser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use terminal_size::{terminal_size, Width};
 
-use crate::board_state::BoardState;
-use crate::generate::generate;
-use crate::play::play;
+use squadro_solver::analysis::{
+    export_csv, first_missing_state, game_tree_dot, merge, print_branching_histogram, print_diff,
+    print_game_value, print_ids, print_longest_forced_win, solve_batch,
+};
+use squadro_solver::board_state::{BoardState, Player, Ruleset};
+use squadro_solver::database::StateDatabase;
+use squadro_solver::file_operations::DataFormat;
+use squadro_solver::generate::{count, generate_with_prefix, Verbosity};
+use squadro_solver::play::{
+    animate_replay, browse_replay, collision_stats, describe_cycle, opening_outcomes, play_match,
+    play_with_database, repetition_cycle, EndReason, PlayOptions,
+};
+use squadro_solver::session::Session;
 
 /// Solver for the Squadro board game
 #[derive(Parser)]
 #[command(version)]
 struct Cli {
+    /// Directory of the data files, the current directory by default
+    #[arg(long, global = true, value_name = "DIR", default_value = "")]
+    data_dir: String,
+
     #[command(subcommand)]
     command: SubCommand,
 }
 
+/// Rules of the game, shared by the subcommands handling board states
+#[derive(Args)]
+struct RulesetArgs {
+    /// Rules of the game, which must be the same to generate and to read the data files
+    ///
+    /// Data files for different rules must be generated with a different `--prefix` or `--data-dir`.
+    #[arg(short, long, value_enum, default_value_t = Rules::Official)]
+    rules: Rules,
+
+    /// Number of pieces a player must bring back to win, which must be the same to generate and to read the data files
+    ///
+    /// Data files for different goals must be generated with a different `--prefix` or `--data-dir`.
+    #[arg(long, value_name = "PIECES", default_value_t = Ruleset::DEFAULT_GOAL, value_parser = clap::value_parser!(u8).range(1..=5).map(usize::from))]
+    goal: usize,
+}
+
+impl RulesetArgs {
+    /// Return the rules selected by `--rules` and `--goal`
+    fn ruleset(&self) -> &'static Ruleset {
+        self.rules.get_ruleset(self.goal)
+    }
+}
+
+/// Rules of the game and names of the data files, shared by the subcommands reading or generating them
+#[derive(Args)]
+struct RulesArgs {
+    #[command(flatten)]
+    ruleset: RulesetArgs,
+
+    /// Prefix added, followed by an underscore, to the names of the data files, which must be the same to generate and to read them
+    ///
+    /// Data files with different prefixes can be generated in the same directory.
+    #[arg(long, default_value = "")]
+    prefix: String,
+}
+
+impl RulesArgs {
+    /// Return the rules selected by `--rules` and `--goal`
+    fn ruleset(&self) -> &'static Ruleset {
+        self.ruleset.ruleset()
+    }
+}
+
 #[derive(Subcommand)]
 enum SubCommand {
     /// Play a game
@@ -30,6 +91,7 @@ enum SubCommand {
         /// Player who makes the first move
         ///
         /// If not specified, the first player is selected at random.
+        /// It can be combined with `--player` to choose both the controlled player and the first player.
         #[arg(short, long, value_enum, value_name = "PLAYER")]
         first: Option<Player>,
 
@@ -42,52 +104,671 @@ enum SubCommand {
         /// Show evaluation of position when computer plays
         #[arg(short, long)]
         eval: bool,
+
+        /// Print the board again after each invalid move
+        #[arg(long)]
+        redraw: bool,
+
+        /// Wait for Enter before each move when the computer plays against itself
+        #[arg(long, conflicts_with_all = ["player", "collision_stats", "openings", "match_games", "replay"])]
+        step: bool,
+
+        /// Print the minimum, average and maximum time taken by the moves of the computer when it plays against itself
+        #[arg(long, conflicts_with_all = ["player", "collision_stats", "openings", "match_games", "replay"])]
+        timings: bool,
+
+        /// Layout of the printed boards
+        #[arg(long, value_enum, default_value_t = Board::Auto)]
+        board: Board,
+
+        #[command(flatten)]
+        rules: RulesArgs,
+
+        /// Session file to resume the game from
+        ///
+        /// If the file exists, the game is resumed from it and `--id` and `--first` are ignored.
+        /// When the human player quits (`q` or end of input), the game is saved to that file.
+        #[arg(long, value_name = "FILE", alias = "resume-session")]
+        session: Option<String>,
+
+        /// Let the computer play the given number of games against itself, without showing them,
+        /// and print how often a move sends a piece back
+        #[arg(long, value_name = "GAMES", conflicts_with_all = ["player", "session"])]
+        collision_stats: Option<usize>,
+
+        /// Let the computer play one game against itself after each possible first move, without showing them,
+        /// and print the outcome of each game
+        #[arg(long, conflicts_with_all = ["player", "session", "collision_stats"])]
+        openings: bool,
+
+        /// Play a match of the given number of games from the initial board state, alternating the first player
+        #[arg(
+            long = "match",
+            value_name = "GAMES",
+            conflicts_with_all = ["id", "session", "collision_stats", "openings"]
+        )]
+        match_games: Option<usize>,
+
+        /// Replay the game saved in a session file (see `--session`), without playing
+        #[arg(
+            long,
+            value_name = "FILE",
+            conflicts_with_all = ["player", "first", "id", "session", "collision_stats", "openings", "match_games"]
+        )]
+        replay: Option<String>,
+
+        /// Delay between two moves of a replayed game, in milliseconds
+        #[arg(long, value_name = "MS", default_value_t = 1000, requires = "replay")]
+        delay: u64,
+
+        /// Browse the replayed game move by move with commands typed on stdin, instead of animating it
+        #[arg(long, requires = "replay", conflicts_with = "delay")]
+        browse: bool,
+
+        /// Highlight the pieces moved by the last move of the replayed game, including those sent back
+        #[arg(long, requires = "replay")]
+        highlight: bool,
+    },
+
+    /// Print the board represented by an ID, without reading the data files
+    Show {
+        /// Board state ID
+        id: u64,
+
+        #[command(flatten)]
+        rules: RulesetArgs,
     },
 
     /// Generate game data (WARNING : memory-intensive and time-consuming process)
-    Generate,
+    Generate {
+        /// Format of the generated data files
+        ///
+        /// When playing, the format of each data file is detected automatically.
+        #[arg(long, value_enum, default_value_t = DataFormat::Zip)]
+        format: DataFormat,
+
+        #[command(flatten)]
+        rules: RulesArgs,
+
+        /// Only count reachable states, without generating data files
+        ///
+        /// This is much faster and less memory-intensive than a full generation.
+        #[arg(long)]
+        count_only: bool,
+
+        /// Number of reachable states printed by `--count-only`, used to show the progress and remaining time of the exploration
+        #[arg(long, value_name = "STATES", conflicts_with_all = ["count_only", "quiet"])]
+        expected: Option<u64>,
+
+        /// Overwrite existing data files instead of stopping (DANGEROUS : existing data is lost)
+        ///
+        /// This is meant for paths managed externally, such as named pipes.
+        /// Only the roaring format can be written to a named pipe, since other formats need to seek in the file.
+        #[arg(long, conflicts_with = "count_only")]
+        no_overwrite_guard: bool,
+
+        /// Also print the time taken by each step
+        #[arg(short, long, conflicts_with = "quiet")]
+        verbose: bool,
+
+        /// Also print the time taken by each step and the peak memory usage after it (only on Linux)
+        #[arg(long, conflicts_with = "quiet")]
+        profile: bool,
+
+        /// Print nothing, except errors
+        #[arg(short, long)]
+        quiet: bool,
+    },
+
+    /// Print who wins with perfect play from the initial board state, for each first player
+    ///
+    /// The data files must have been generated for the full game.
+    GameValue {
+        #[command(flatten)]
+        rules: RulesArgs,
+    },
+
+    /// Print the states with the longest forced win, and the moves to the end of the game from the first one
+    ///
+    /// The winner plays the fastest win and the loser the longest defense.
+    /// All states are loaded in memory, so the data files should be generated from a subtree.
+    LongestWin {
+        #[command(flatten)]
+        rules: RulesArgs,
+    },
+
+    /// Print the game tree from a board state in the DOT language of Graphviz, with nodes colored by winner
+    Dot {
+        /// Board state ID at the root of the tree
+        id: u64,
+
+        /// Maximum number of moves from the root
+        #[arg(short, long, default_value_t = 2)]
+        depth: u32,
+
+        #[command(flatten)]
+        rules: RulesArgs,
+    },
+
+    /// Check the data files, e.g. before playing from a subtree
+    Verify {
+        /// Check that all board states reachable from this ID are in the data files, which then cover any game from it
+        #[arg(long, value_name = "ID")]
+        validate_closure: u64,
+
+        #[command(flatten)]
+        rules: RulesArgs,
+    },
+
+    /// Print the winner of many board states, read one ID per line, as `id,verdict` lines
+    ///
+    /// The verdict is `top`, `left`, `draw`, `unknown` (state absent from the data files) or `invalid` (not an ID).
+    SolveBatch {
+        /// File with one board state ID per line, or standard input if not provided
+        file: Option<String>,
+
+        /// Prefix of the names of the data files, which must be the one used to generate them
+        #[arg(long, default_value = "")]
+        prefix: String,
+    },
+
+    /// Print all states of the data files as CSV, with their next player, pieces home and winner
+    ExportCsv {
+        /// Maximum number of printed states
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+
+        #[command(flatten)]
+        rules: RulesArgs,
+    },
+
+    /// Print how many states of a data file have each number of legal moves
+    Branching {
+        /// Data file
+        file: String,
+
+        #[command(flatten)]
+        rules: RulesetArgs,
+    },
+
+    /// Print all states stored in a data file, in increasing order, one ID per line
+    ListIds {
+        /// Data file
+        file: String,
+
+        /// Maximum number of printed IDs
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+    },
+
+    /// Write a new data file with all states stored in at least one of the given data files
+    ///
+    /// This combines data files generated from different subtrees, file by file, without exploring them again.
+    Merge {
+        /// Data files to merge
+        #[arg(required = true)]
+        inputs: Vec<String>,
+
+        /// Data file to create, which must not exist yet
+        #[arg(short, long)]
+        output: String,
+
+        /// Format of the created data file
+        #[arg(long, value_enum, default_value_t = DataFormat::Zip)]
+        format: DataFormat,
+    },
+
+    /// Compare two data files and print the states stored in only one of them
+    Diff {
+        /// First data file
+        a: String,
+
+        /// Second data file
+        b: String,
+
+        /// Maximum number of differing IDs printed for each file
+        #[arg(short, long, default_value_t = 10)]
+        sample: usize,
+    },
+}
+
+// Moves after which a game is considered a draw when collecting statistics.
+const MAX_MOVES: usize = 1000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
+enum Rules {
+    /// Official rules
+    Official,
+
+    /// Pieces of both players move at the speeds of the top player's pieces
+    IdenticalFirstMoves,
+}
+
+impl Rules {
+    /// Return the rules in which a player must bring back `goal` pieces to win
+    ///
+    /// Rules with a non-default goal are leaked once, then the same reference is returned for the same `goal`.
+    fn get_ruleset(&self, goal: usize) -> &'static Ruleset {
+        static LEAKED_RULESETS: OnceLock<Mutex<HashMap<(Rules, usize), &'static Ruleset>>> =
+            OnceLock::new();
+
+        let ruleset = match self {
+            Rules::Official => &Ruleset::OFFICIAL,
+            Rules::IdenticalFirstMoves => &Ruleset::IDENTICAL_FIRST_MOVES,
+        };
+
+        if goal == ruleset.get_goal() {
+            ruleset
+        } else {
+            // Board states keep a reference to their rules until the end of the program.
+            LEAKED_RULESETS
+                .get_or_init(Default::default)
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .entry((*self, goal))
+                .or_insert_with(|| Box::leak(Box::new(ruleset.clone().with_goal(goal))))
+        }
+    }
 }
 
-#[repr(usize)]
 #[derive(Clone, ValueEnum)]
-enum Player {
-    /// Top player
-    Top = 0,
+enum Board {
+    /// Compact board if the output is a terminal narrower than the full board, full board otherwise
+    Auto,
+
+    /// Full board, drawn with box-drawing characters
+    Full,
 
-    /// Left player
-    Left = 1,
+    /// Compact board in plain ASCII, with one progress bar per piece
+    Compact,
+}
+
+impl Board {
+    /// Return whether boards should be printed with `BoardState::render_compact`
+    fn is_compact(&self) -> bool {
+        match self {
+            Board::Auto => terminal_size()
+                .is_some_and(|(Width(width), _)| usize::from(width) < BoardState::DISPLAY_WIDTH),
+            Board::Full => false,
+            Board::Compact => true,
+        }
+    }
 }
 
 fn main() {
-    match Cli::parse().command {
+    let cli = Cli::parse();
+    let data_dir = cli.data_dir;
+
+    match cli.command {
         SubCommand::Play {
             player,
             first,
             id,
             eval,
+            redraw,
+            step,
+            timings,
+            board,
+            rules,
+            session,
+            collision_stats: collision_stats_games,
+            openings,
+            match_games,
+            replay,
+            delay,
+            browse,
+            highlight,
         } => {
-            play(
-                // If `id` is provided, play from that board state ID.
-                // Otherwise, if `first` is provided, play a game from
-                // the initial board state, with the given first player.
-                // When neither of these arguments is provided, play a game
-                // from the initial board state, with a random first player.
-                id.unwrap_or_else(|| {
-                    BoardState::new_game(first.unwrap_or_else(|| {
-                        if fastrand::bool() {
-                            Player::Left
-                        } else {
-                            Player::Top
-                        }
-                    }) as usize)
+            let ruleset = rules.ruleset();
+            let database = StateDatabase::with_prefix(&data_dir, &rules.prefix);
+            let options = PlayOptions {
+                show_eval: eval,
+                redraw,
+                step,
+                timings,
+                compact: board.is_compact(),
+            };
+
+            if let Some(path) = replay {
+                let states = Session::load(&path)
+                    .unwrap_or_else(|e| panic!("{}", e))
+                    .replay(ruleset)
+                    .unwrap_or_else(|| panic!("Invalid move in session file"));
+
+                if browse {
+                    browse_replay(&states, highlight);
+                } else {
+                    animate_replay(&states, Duration::from_millis(delay), highlight);
+                }
+
+                if let Some(cycle) = repetition_cycle(&states) {
+                    println!(
+                        "\nA position was repeated after this cycle of {} moves : {}.",
+                        cycle.len(),
+                        describe_cycle(&cycle)
+                    );
+                }
+                return;
+            }
+
+            if let Some(games) = match_games {
+                let first_player = first.unwrap_or_else(Player::random);
+
+                play_match(
+                    &database,
+                    &[Player::Top, Player::Left]
+                        .map(|p| BoardState::new_game_for_with_ruleset(p, ruleset)),
+                    games,
+                    first_player.into(),
+                    player.map(usize::from),
+                    &options,
+                );
+                return;
+            }
+
+            // If `id` is provided, play from that board state ID.
+            // Otherwise, if `first` is provided, play a game from
+            // the initial board state, with the given first player.
+            // When neither of these arguments is provided, play a game
+            // from the initial board state, with a random first player.
+            let init_id = id.unwrap_or_else(|| {
+                BoardState::new_game_for_with_ruleset(first.unwrap_or_else(Player::random), ruleset)
                     .get_id()
-                }),
-                player.map(|p| p as usize),
-                eval,
+            });
+
+            if let Some(games) = collision_stats_games {
+                let (collisions, moves) =
+                    collision_stats(&database, init_id, ruleset, games, MAX_MOVES);
+                println!(
+                    "{} collisions in {} moves ({:.2}%).",
+                    collisions,
+                    moves,
+                    100.0 * collisions as f64 / moves.max(1) as f64
+                );
+                return;
+            }
+
+            if openings {
+                let init_state = BoardState::from_id_with_ruleset(init_id, ruleset);
+                for (piece, winner_opt) in opening_outcomes(&database, &init_state) {
+                    let outcome = match winner_opt {
+                        Some(0) => "top player wins",
+                        Some(_) => "left player wins",
+                        None => "draw",
+                    };
+                    println!("Opening move {} : {}.", piece, outcome);
+                }
+                return;
+            }
+
+            let mut current_session = match &session {
+                Some(path) if Path::new(path).exists() => {
+                    Session::load(path).unwrap_or_else(|e| panic!("{}", e))
+                }
+                _ => Session::new(init_id),
+            };
+
+            let resumed_states = current_session
+                .replay(ruleset)
+                .unwrap_or_else(|| panic!("Invalid move in session file"));
+
+            let (all_states, _, end_reason) = play_with_database(
+                &database,
+                resumed_states
+                    .last()
+                    .expect("There should be at least one state")
+                    .get_id(),
+                ruleset,
+                player.map(usize::from),
+                &options,
             );
+
+            // Save the game if the human player quit before its end.
+            if let Some(path) = session {
+                if end_reason != EndReason::Finished {
+                    current_session.extend_from_states(&all_states);
+                    current_session
+                        .save(&path)
+                        .unwrap_or_else(|e| panic!("Unable to save session : {}", e));
+                    println!("Game saved to {}", path);
+                }
+            }
+        }
+        SubCommand::Show { id, rules } => {
+            let ruleset = rules.ruleset();
+            let state = BoardState::from_id_with_ruleset(id, ruleset);
+
+            // The board is still printed, since it may help to find what is wrong with the ID.
+            if id > BoardState::max_valid_id_with_ruleset(ruleset) {
+                eprintln!(
+                    "Warning : ID out of range, the board may be malformed : {}",
+                    id
+                );
+            } else if !state.is_well_formed() {
+                eprintln!(
+                    "Warning : this board state can't occur in a game (e.g. two pieces share a square)"
+                );
+            }
+
+            println!("{}", state);
+        }
+        SubCommand::Generate {
+            format,
+            rules,
+            count_only,
+            expected,
+            no_overwrite_guard,
+            verbose,
+            profile,
+            quiet,
+        } => {
+            let ruleset = rules.ruleset();
+            let init_states = [Player::Top, Player::Left]
+                .map(|p| BoardState::new_game_for_with_ruleset(p, ruleset));
+
+            if count_only {
+                count(&init_states);
+            } else {
+                let verbosity = if quiet {
+                    Verbosity::Quiet
+                } else if profile {
+                    Verbosity::Profile
+                } else if verbose {
+                    Verbosity::Verbose
+                } else {
+                    Verbosity::Normal
+                };
+
+                generate_with_prefix(
+                    &init_states,
+                    format,
+                    verbosity,
+                    &data_dir,
+                    &rules.prefix,
+                    expected,
+                    !no_overwrite_guard,
+                );
+            }
+        }
+        SubCommand::GameValue { rules } => print_game_value(
+            &StateDatabase::with_prefix(&data_dir, &rules.prefix),
+            rules.ruleset(),
+        ),
+        SubCommand::LongestWin { rules } => print_longest_forced_win(
+            &StateDatabase::with_prefix(&data_dir, &rules.prefix),
+            rules.ruleset(),
+        ),
+        SubCommand::Dot { id, depth, rules } => {
+            let root = BoardState::from_id_with_ruleset(id, rules.ruleset());
+            let dot = game_tree_dot(
+                &StateDatabase::with_prefix(&data_dir, &rules.prefix),
+                &root,
+                depth,
+            )
+            .unwrap_or_else(|e| panic!("{}", e));
+            print!("{}", dot);
+        }
+        SubCommand::Verify {
+            validate_closure,
+            rules,
+        } => {
+            let root = BoardState::from_id_with_ruleset(validate_closure, rules.ruleset());
+            match first_missing_state(&StateDatabase::with_prefix(&data_dir, &rules.prefix), &root)
+                .unwrap_or_else(|e| panic!("{}", e))
+            {
+                None => println!(
+                    "All states reachable from ID {} are in the data files.",
+                    validate_closure
+                ),
+                Some(id) => panic!("Board state ID not found in data files : {}", id),
+            }
+        }
+        SubCommand::SolveBatch { file, prefix } => {
+            let database = StateDatabase::with_prefix(&data_dir, &prefix);
+            let mut stdout = io::stdout().lock();
+            let result = match file {
+                Some(path) => {
+                    let file = File::open(&path).unwrap_or_else(|e| panic!("{} : {}", path, e));
+                    solve_batch(&database, BufReader::new(file), &mut stdout)
+                }
+                None => solve_batch(&database, io::stdin().lock(), &mut stdout),
+            };
+            result.unwrap_or_else(|e| panic!("{}", e));
         }
-        SubCommand::Generate => {
-            generate(&([Player::Top, Player::Left].map(|p| BoardState::new_game(p as usize))));
+        SubCommand::ExportCsv { limit, rules } => {
+            export_csv(
+                &StateDatabase::with_prefix(&data_dir, &rules.prefix),
+                rules.ruleset(),
+                limit,
+                &mut io::stdout().lock(),
+            )
+            .unwrap_or_else(|e| panic!("{}", e));
         }
+        SubCommand::Branching { file, rules } => print_branching_histogram(&file, rules.ruleset()),
+        SubCommand::ListIds { file, limit } => print_ids(&file, limit),
+        SubCommand::Merge {
+            inputs,
+            output,
+            format,
+        } => {
+            let states = merge(&inputs, &output, format).unwrap_or_else(|e| panic!("{}", e));
+            println!("{} states saved to {}.", states, output);
+        }
+        SubCommand::Diff { a, b, sample } => print_diff(&a, &b, sample),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn cli() {
+        Cli::command().debug_assert();
+
+        // The controlled player and the first player can be chosen independently.
+        let cli = Cli::try_parse_from([
+            "squadro-solver",
+            "play",
+            "--player",
+            "top",
+            "--first",
+            "left",
+        ])
+        .unwrap();
+        assert!(matches!(
+            cli.command,
+            SubCommand::Play {
+                player: Some(Player::Top),
+                first: Some(Player::Left),
+                id: None,
+                ..
+            }
+        ));
+
+        // The first player is already included in the board state ID.
+        assert!(
+            Cli::try_parse_from(["squadro-solver", "play", "--id", "4", "--first", "left"])
+                .is_err()
+        );
+        assert!(
+            Cli::try_parse_from(["squadro-solver", "play", "--id", "4", "--player", "left"])
+                .is_ok()
+        );
+
+        let cli = Cli::try_parse_from(["squadro-solver", "play", "--match", "3", "--first", "top"])
+            .unwrap();
+        assert!(matches!(
+            cli.command,
+            SubCommand::Play {
+                match_games: Some(3),
+                first: Some(Player::Top),
+                ..
+            }
+        ));
+        assert!(
+            Cli::try_parse_from(["squadro-solver", "play", "--match", "3", "--id", "4"]).is_err()
+        );
+
+        let cli = Cli::try_parse_from(["squadro-solver", "show", "85065666045"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            SubCommand::Show {
+                id: 85065666045,
+                ..
+            }
+        ));
+        assert!(Cli::try_parse_from(["squadro-solver", "show"]).is_err());
+
+        // Subcommands without data files take the rules, but no prefix.
+        let cli = Cli::try_parse_from(["squadro-solver", "show", "4", "--goal", "3"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            SubCommand::Show { rules, .. } if rules.ruleset().get_goal() == 3
+        ));
+        assert!(Cli::try_parse_from(["squadro-solver", "show", "4", "--prefix", "short"]).is_err());
+
+        let cli = Cli::try_parse_from(["squadro-solver", "generate", "--goal", "2"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            SubCommand::Generate {
+                rules: RulesArgs {
+                    ruleset: RulesetArgs { goal: 2, .. },
+                    ..
+                },
+                ..
+            }
+        ));
+
+        let cli = Cli::try_parse_from(["squadro-solver", "play", "--prefix", "short"]).unwrap();
+        assert!(matches!(cli.command, SubCommand::Play { rules, .. } if rules.prefix == "short"));
+        assert_eq!(cli.data_dir, "");
+
+        let cli =
+            Cli::try_parse_from(["squadro-solver", "game-value", "--data-dir", "data"]).unwrap();
+        assert_eq!(cli.data_dir, "data");
+        for goal in ["0", "6"] {
+            assert!(Cli::try_parse_from(["squadro-solver", "play", "--goal", goal]).is_err());
+        }
+        assert_eq!(
+            Rules::Official.get_ruleset(Ruleset::DEFAULT_GOAL),
+            &Ruleset::OFFICIAL
+        );
+        assert_eq!(Rules::Official.get_ruleset(2).get_goal(), 2);
+
+        // A ruleset with a non-default goal is only leaked once.
+        assert!(std::ptr::eq(
+            Rules::Official.get_ruleset(2),
+            Rules::Official.get_ruleset(2)
+        ));
+        assert!(!std::ptr::eq(
+            Rules::Official.get_ruleset(2),
+            Rules::Official.get_ruleset(3)
+        ));
+        assert_ne!(
+            Rules::Official.get_ruleset(2),
+            Rules::IdenticalFirstMoves.get_ruleset(2)
+        );
     }
 }
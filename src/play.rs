@@ -1,40 +1,92 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::io::{self, BufRead, Write};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::board_state::BoardState;
-use crate::file_operations;
+use crate::analysis;
+use crate::board_state::{BoardState, Move, Ruleset};
+use crate::database::{BoardStateEval, StateDatabase};
 
-/// Evaluation of the board state
-#[derive(Debug, PartialEq)]
-enum BoardStateEval {
-    Win,
-    Draw, // Endless game.
-    Loss,
+/// Reason why a game stopped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EndReason {
+    /// The game reached an ended board state
+    Finished,
+
+    /// The human player deliberately quit the game
+    Resigned,
+
+    /// The human player's input was closed before the end of the game
+    InputClosed,
 }
 
-/// Play a game, starting from the board state represented by `init_id`
+/// Display options of a game, all disabled by default
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PlayOptions {
+    /// Print the evaluation of the last player after each computer move
+    pub show_eval: bool,
+
+    /// Print the board again after each invalid move of the human player
+    pub redraw: bool,
+
+    /// When the computer plays against itself, wait for the user to press Enter before each move
+    pub step: bool,
+
+    /// When the computer plays against itself, print the time taken by its moves at the end
+    pub timings: bool,
+
+    /// Print boards with `BoardState::render_compact`, for narrow terminals
+    pub compact: bool,
+}
+
+// Result of a move : the next state, or the reason why no move was made.
+type MoveResult = (Result<BoardState, EndReason>, Option<BoardStateEval>);
+
+/// Play a game following `ruleset`, starting from the board state represented by `init_id`
 ///
-/// Return all states encountered during the game and the winner of the game.
+/// Return all states encountered during the game, the winner of the game and the reason why it stopped.
 pub fn play(
     init_id: u64,
+    ruleset: &'static Ruleset,
     human_player_opt: Option<usize>,
-    show_eval: bool,
-) -> (Vec<BoardState>, usize) {
-    abort_if_id_is_invalid(init_id);
+    options: &PlayOptions,
+) -> (Vec<BoardState>, usize, EndReason) {
+    play_with_database(
+        &StateDatabase::in_current_dir(),
+        init_id,
+        ruleset,
+        human_player_opt,
+        options,
+    )
+}
 
-    let init_state = BoardState::from(init_id);
+/// Same as `play`, but look up board states in `database` instead of the data files of the current directory
+///
+/// A single database can be shared by many games.
+pub fn play_with_database(
+    database: &StateDatabase,
+    init_id: u64,
+    ruleset: &'static Ruleset,
+    human_player_opt: Option<usize>,
+    options: &PlayOptions,
+) -> (Vec<BoardState>, usize, EndReason) {
+    abort_if_id_is_invalid(database, init_id, ruleset);
+
+    let init_state = BoardState::from_id_with_ruleset(init_id, ruleset);
     match human_player_opt {
         Some(human_player) => {
             // Start playing against computer.
-            let (all_states, winner) = print_all_states(
+            let (all_states, winner, end_reason) = print_all_states(
                 init_state,
-                &|state: BoardState| -> (Option<BoardState>, Option<BoardStateEval>) {
+                &|state: BoardState| -> MoveResult {
                     if state.get_next_player() == human_player {
-                        get_next_state_from_user_input(state, io::stdin().lock())
+                        get_next_state_from_user_input(state, io::stdin().lock(), options)
                     } else {
-                        get_best_next_state(state)
+                        get_best_next_state(database, state)
                     }
                 },
-                show_eval,
+                options,
             );
 
             if winner == human_player {
@@ -43,53 +95,154 @@ pub fn play(
                 println!("\nComputer wins!");
             }
 
-            (all_states, winner)
+            (all_states, winner, end_reason)
         }
         None => {
             // Start computer self-play.
-            print_all_states(init_state, &get_best_next_state, show_eval)
+            let move_durations = RefCell::new(Vec::new());
+            let result = print_all_states(
+                init_state,
+                &|state| {
+                    if options.step && !wait_for_step(io::stdin().lock()) {
+                        return (Err(EndReason::InputClosed), None);
+                    }
+
+                    // Only the time spent choosing the move is measured, not the time spent waiting for the user.
+                    let start = Instant::now();
+                    let move_result = get_best_next_state(database, state);
+                    move_durations.borrow_mut().push(start.elapsed());
+                    move_result
+                },
+                options,
+            );
+
+            if let (true, Some((min, avg, max))) =
+                (options.timings, duration_stats(&move_durations.borrow()))
+            {
+                println!(
+                    "\nTime per computer move : min {:.2?}, avg {:.2?}, max {:.2?} ({} moves)",
+                    min,
+                    avg,
+                    max,
+                    move_durations.borrow().len()
+                );
+            }
+
+            result
         }
     }
 }
 
+/// Play `games` games, alternating the first player, and return the number of wins of each player
+///
+/// Board states are looked up in `database`. The first game starts from `init_states[first_player]`, the second one from `init_states[1 - first_player]`, and so on.
+/// Each state of `init_states` must therefore be a board state where the player with the same number moves first.
+/// Every game is played with `options`.
+pub fn play_match(
+    database: &StateDatabase,
+    init_states: &[BoardState; 2],
+    games: usize,
+    first_player: usize,
+    human_player_opt: Option<usize>,
+    options: &PlayOptions,
+) -> [usize; 2] {
+    let mut wins = [0; 2];
+
+    for game in 0..games {
+        let player = (first_player + game) % 2;
+        println!("\n=== Game {} of {} ===\n", game + 1, games);
+
+        let (_, winner, _) = play_with_database(
+            database,
+            init_states[player].get_id(),
+            init_states[player].get_ruleset(),
+            human_player_opt,
+            options,
+        );
+        wins[winner] += 1;
+
+        println!("\nScore : top {} - {} left", wins[0], wins[1]);
+    }
+
+    wins
+}
+
 /// Starting from `init_state`, print states provided by `get_next_state` and stop when the game ends
 ///
-/// Return all printed states and the winner of the game.
+/// Only the `show_eval` and `compact` options are used. Return all printed states, the winner of the game and the reason why it stopped.
 fn print_all_states(
     init_state: BoardState,
-    get_next_state: &dyn Fn(BoardState) -> (Option<BoardState>, Option<BoardStateEval>),
-    show_eval: bool,
-) -> (Vec<BoardState>, usize) {
+    get_next_state: &dyn Fn(BoardState) -> MoveResult,
+    options: &PlayOptions,
+) -> (Vec<BoardState>, usize, EndReason) {
     let mut state = init_state;
     let mut all_states = vec![state.clone()];
 
-    println!("{}", state);
+    println!("{}", render_board(&state, options.compact));
 
     while !state.is_ended() {
-        let (state_opt, eval_opt) = get_next_state(state.clone());
-        if state_opt.is_none() {
-            println!("\n(Player resigned)");
-            break;
-        }
-        state = state_opt.expect("The state should exist");
+        let (state_result, eval_opt) = get_next_state(state.clone());
+        state = match state_result {
+            Ok(next_state) => next_state,
+            Err(end_reason) => {
+                match end_reason {
+                    EndReason::InputClosed => println!("\n(Input closed)"),
+                    _ => println!("\n(Player resigned)"),
+                }
+                return (all_states, 1 - state.get_next_player(), end_reason);
+            }
+        };
 
         all_states.push(state.clone());
 
-        println!("\n{}", state);
+        println!("\n{}", render_board(&state, options.compact));
 
-        if let (true, Some(eval)) = (show_eval, eval_opt) {
+        if let (true, Some(eval)) = (options.show_eval, eval_opt) {
             println!("(Last player's evaluation : {:?})", eval);
         }
     }
 
-    (all_states, 1 - state.get_next_player())
+    (all_states, 1 - state.get_next_player(), EndReason::Finished)
+}
+
+/// Return the minimum, average and maximum of `durations`, or `None` if there are none
+fn duration_stats(durations: &[Duration]) -> Option<(Duration, Duration, Duration)> {
+    let min = *durations.iter().min()?;
+    let max = *durations.iter().max()?;
+    let avg = durations.iter().sum::<Duration>() / durations.len() as u32;
+
+    Some((min, avg, max))
+}
+
+/// Return `state` as displayed on a terminal, with `BoardState::render_compact` if `compact` is set
+fn render_board(state: &BoardState, compact: bool) -> String {
+    if compact {
+        state.render_compact()
+    } else {
+        state.to_string()
+    }
+}
+
+/// Wait for the user to press Enter before the next move, and return `false` if their input was closed instead
+fn wait_for_step(mut reader: impl BufRead) -> bool {
+    print!("\n(Press Enter to play the next move)");
+    io::stdout().flush().expect("stdout should be writable");
+
+    let mut input = String::new();
+    !matches!(reader.read_line(&mut input), Ok(0))
 }
 
 /// Ask the user for their next move and return the corresponding next state
+///
+/// Only the `redraw` and `compact` options are used: the board is printed again after an invalid move if `redraw` is set.
+/// Entering `q` or `quit` resigns the game. The end of input, even in the middle of a line, closes the game.
+/// Entering `?` followed by a piece prints the board after that piece's move, without playing it.
+/// Pieces are given by number, or by column (top player) or row (left player) counted from 1, e.g. `column 2`.
 fn get_next_state_from_user_input(
     state: BoardState,
     mut reader: impl BufRead,
-) -> (Option<BoardState>, Option<BoardStateEval>) {
+    options: &PlayOptions,
+) -> MoveResult {
     loop {
         print!("\nYour move : "); // Without flushing, that string is printed after user input.
         io::stdout().flush().expect("stdout should be writable");
@@ -97,14 +250,36 @@ fn get_next_state_from_user_input(
         // Read user input from stdin.
         let mut input = String::new();
         match reader.read_line(&mut input) {
-            Ok(0) => return (None, None), // End of user input.
+            Ok(0) => return (Err(EndReason::InputClosed), None), // End of user input.
             Ok(_) => {
-                if let Ok(input_usize) = input.trim().parse::<usize>() {
-                    if let Some(next_state) = state.get_next_state(input_usize) {
+                if matches!(input.trim(), "q" | "quit") {
+                    return (Err(EndReason::Resigned), None);
+                }
+
+                if let Some(preview) = input.trim().strip_prefix('?') {
+                    if let Some(next_state) = parse_piece(preview, state.get_next_player())
+                        .and_then(|next_move| state.get_next_state_for(next_move))
+                    {
+                        // Show the resulting state, then ask for a move again.
+                        print!(
+                            "\n{}\n(Preview only, the move is not played yet)",
+                            render_board(&next_state, options.compact)
+                        );
+                        continue;
+                    }
+                }
+
+                if let Some(next_move) = parse_piece(&input, state.get_next_player()) {
+                    if let Some(next_state) = state.get_next_state_for(next_move) {
                         // If the user-given piece is valid, return the corresponding state.
-                        return (Some(next_state), None);
+                        return (Ok(next_state), None);
                     }
                 }
+
+                if !input.ends_with('\n') {
+                    // Invalid last line, with no line break since user input ended.
+                    return (Err(EndReason::InputClosed), None);
+                }
             }
             Err(e) => match e.kind() {
                 io::ErrorKind::InvalidData => {} // Invalid UTF-8 byte sequence.
@@ -112,59 +287,505 @@ fn get_next_state_from_user_input(
             },
         };
 
-        let available_pieces = (0..5)
-            .filter_map(|p| state.get_next_state(p).map(|_| p.to_string()))
-            .collect::<Vec<String>>()
-            .join(", ");
-        print!("Invalid move! Available piece(s) : {}", available_pieces);
+        if options.redraw {
+            println!("\n\n{}", render_board(&state, options.compact));
+        }
+
+        let movable_labels = state.movable_labels();
+        let join = |labels: Vec<String>| labels.join(", ");
+        print!(
+            "Invalid move! Available piece(s) : {} (or {} {})",
+            join(movable_labels.iter().map(|p| p.to_string()).collect()),
+            ["column(s)", "row(s)"][state.get_next_player()],
+            join(movable_labels.iter().map(|p| (p + 1).to_string()).collect())
+        );
     }
 }
 
-/// Return a next state that gives the best final outcome for the next player
-fn get_best_next_state(state: BoardState) -> (Option<BoardState>, Option<BoardStateEval>) {
-    let next_player = state.get_next_player();
+/// Return the piece of `player` designated by `input`
+///
+/// A piece is designated by its number, or by its column (top player) or row (left player) counted from 1.
+/// Return `None` if `input` doesn't designate a piece, or designates one which doesn't exist.
+fn parse_piece(input: &str, player: usize) -> Option<Move> {
+    let mut words = input.split_whitespace();
+    let first_word = words.next()?;
+
+    let piece = match (first_word.to_lowercase().as_str(), player) {
+        ("column" | "col", 0) | ("row", 1) => {
+            words.next()?.parse::<usize>().ok()?.checked_sub(1)?
+        }
+        _ => first_word.parse().ok()?,
+    };
+
+    // Anything after the piece is invalid.
+    match words.next() {
+        Some(_) => None,
+        None => Move::new(piece),
+    }
+}
 
+/// Return a next state that gives the best final outcome for the next player, according to `database`
+fn get_best_next_state(database: &StateDatabase, state: BoardState) -> MoveResult {
     let mut next_states: Vec<BoardState> = state.get_next_states().collect();
     fastrand::shuffle(&mut next_states);
 
-    // Look for a winning state in `next_states`.
-    for next_state in &next_states {
-        if file_operations::read_state_value(
-            file_operations::WINNING_STATES_PATH[next_player],
-            next_state.get_id(),
-        ) {
-            // Return a winning state.
-            return (Some(next_state.clone()), Some(BoardStateEval::Win));
+    select_best_next_state(database, state.get_next_player(), &next_states)
+}
+
+/// Return the first state of `next_states` that gives the best final outcome for `next_player`, according to `database`
+///
+/// States missing from `database` are preferred to losing states. Among them, the state in which
+/// the pieces of `next_player` need the fewest moves to finish is selected.
+fn select_best_next_state(
+    database: &StateDatabase,
+    next_player: usize,
+    next_states: &[BoardState],
+) -> MoveResult {
+    let verdicts: Vec<BoardStateEval> = next_states
+        .iter()
+        .map(|next_state| {
+            database
+                .verdict(next_state.get_id(), next_player)
+                .unwrap_or_else(|e| panic!("{}", e))
+        })
+        .collect();
+    let find_state = |eval| {
+        next_states
+            .iter()
+            .zip(&verdicts)
+            .find(|&(_, &verdict)| verdict == eval)
+            .map(|(next_state, _)| next_state)
+    };
+
+    // Return a winning state, or else a drawing state.
+    for eval in [BoardStateEval::Win, BoardStateEval::Draw] {
+        if let Some(next_state) = find_state(eval) {
+            return (Ok(next_state.clone()), Some(eval));
         }
     }
 
-    // Look for a non-winning state (for the previous player) in `next_states`.
-    for next_state in &next_states {
-        if !file_operations::read_state_value(
-            file_operations::WINNING_STATES_PATH[1 - next_player],
-            next_state.get_id(),
-        ) {
-            // Return a drawing state.
-            return (Some(next_state.clone()), Some(BoardStateEval::Draw));
-        }
+    // Fall back to the state in which `next_player` is closest to finishing, among unknown states.
+    let unknown_state_opt = next_states
+        .iter()
+        .zip(&verdicts)
+        .filter(|&(_, &verdict)| verdict == BoardStateEval::Unknown)
+        .map(|(next_state, _)| next_state)
+        .min_by_key(|next_state| {
+            (0..5)
+                .map(|piece| next_state.remaining_moves(next_player, piece))
+                .sum::<usize>()
+        });
+    if let Some(unknown_state) = unknown_state_opt {
+        return (Ok(unknown_state.clone()), Some(BoardStateEval::Unknown));
     }
 
     // Return a losing state.
     (
-        Some(
-            next_states
-                .first()
-                .expect("There should be at least one next state")
-                .clone(),
-        ),
+        Ok(next_states
+            .first()
+            .expect("There should be at least one next state")
+            .clone()),
         Some(BoardStateEval::Loss),
     )
 }
 
-/// Terminate thread if `id` does not represent a valid board state
-fn abort_if_id_is_invalid(id: u64) {
-    if !file_operations::read_state_value(file_operations::ALL_STATES_PATH, id) {
-        panic!("Invalid board state ID : {}", id);
+/// Return the moves of the next player which lead to a draw, according to `database`
+pub fn drawing_moves(database: &StateDatabase, state: &BoardState) -> Vec<Move> {
+    state
+        .movable_moves()
+        .into_iter()
+        .filter(|&next_move| {
+            let next_id = state
+                .get_next_state_for(next_move)
+                .expect("Movable pieces have a next state")
+                .get_id();
+
+            database
+                .verdict(next_id, state.get_next_player())
+                .unwrap_or_else(|e| panic!("{}", e))
+                == BoardStateEval::Draw
+        })
+        .collect()
+}
+
+/// Return whether every move of the next player is worse for them than passing, according to `database`
+///
+/// The verdict of a state is the one of its best move, so moves are compared to the same state with the other player
+/// to move instead. Return `false` for ended states and when a verdict is unknown.
+pub fn is_zugzwang(database: &StateDatabase, state: &BoardState) -> bool {
+    if state.is_ended() {
+        return false;
+    }
+
+    let player = state.get_next_player();
+    let verdict = |id| {
+        database
+            .verdict(id, player)
+            .unwrap_or_else(|e| panic!("{}", e))
+    };
+
+    let passing_verdict = verdict(state.passed().get_id());
+    state
+        .get_next_states()
+        .all(|next_state| verdict(next_state.get_id()) < passing_verdict)
+}
+
+/// Return each piece the next player of `state` can move, with the outcome for that player of the resulting state
+///
+/// Outcomes are read from `database`, and can be sorted from best to worst since Win > Draw > Loss
+/// (see `BoardStateEval`), unknown outcomes not being comparable. Pieces are in increasing order.
+pub fn move_verdicts(database: &StateDatabase, state: &BoardState) -> Vec<(usize, BoardStateEval)> {
+    let player = state.get_next_player();
+
+    state
+        .movable_labels()
+        .into_iter()
+        .map(|piece| {
+            let next_state = state
+                .get_next_state(piece)
+                .expect("Movable pieces have a next state");
+            let verdict = database
+                .verdict(next_state.get_id(), player)
+                .unwrap_or_else(|e| panic!("{}", e));
+
+            (piece, verdict)
+        })
+        .collect()
+}
+
+/// Return the only move the next player can play, or the only one which doesn't lose according to `database_opt`
+///
+/// Return `None` when the next player has a real choice, i.e. several moves, or several non-losing moves.
+pub fn forced_move(database_opt: Option<&StateDatabase>, state: &BoardState) -> Option<Move> {
+    let movable_moves = state.movable_moves();
+    if movable_moves.len() == 1 {
+        return Some(movable_moves[0]);
+    }
+
+    let database = database_opt?;
+    let non_losing_moves: Vec<Move> = movable_moves
+        .into_iter()
+        .filter(|&next_move| {
+            let next_id = state
+                .get_next_state_for(next_move)
+                .expect("Movable pieces have a next state")
+                .get_id();
+
+            database
+                .verdict(next_id, state.get_next_player())
+                .unwrap_or_else(|e| panic!("{}", e))
+                != BoardStateEval::Loss
+        })
+        .collect();
+
+    match non_losing_moves[..] {
+        [next_move] => Some(next_move),
+        _ => None,
+    }
+}
+
+/// Return the line of best moves played by both players from `init_state`, as pairs of move and resulting state
+///
+/// From a won state, the winner plays the fastest win and the loser the longest defense until the game ends (see
+/// `analysis::forced_win_line`). Depths are computed with `analysis::depths_to_win`, so this is meant for data files
+/// generated from a subtree. Otherwise, each player plays the first drawing piece, and the line stops when a state
+/// is repeated (which means the game is a draw).
+pub fn principal_variation(
+    database: &StateDatabase,
+    init_state: BoardState,
+) -> Vec<(Move, BoardState)> {
+    abort_if_id_is_invalid(database, init_state.get_id(), init_state.get_ruleset());
+
+    let winner_opt = database
+        .evaluate(init_state.get_id())
+        .unwrap_or_else(|e| panic!("{}", e));
+    if winner_opt.is_some() {
+        let depths = analysis::depths_to_win(database, init_state.get_ruleset())
+            .unwrap_or_else(|e| panic!("{}", e));
+        return analysis::forced_win_line(&depths, &init_state);
+    }
+
+    let mut line = Vec::new();
+    let mut seen_ids = HashSet::from([init_state.get_id()]);
+    let mut state = init_state;
+
+    while !state.is_ended() {
+        // Next states are kept in piece order, so that the line is always the same.
+        let next_states: Vec<BoardState> = state.get_next_states().collect();
+        let next_state = select_best_next_state(database, state.get_next_player(), &next_states)
+            .0
+            .expect("There should be a next state");
+
+        let next_move = state
+            .moved_piece_to(&next_state)
+            .expect("The next state should be reachable by moving a piece");
+
+        let is_repeated = !seen_ids.insert(next_state.get_id());
+        line.push((next_move, next_state.clone()));
+        if is_repeated {
+            break;
+        }
+
+        state = next_state;
+    }
+
+    line
+}
+
+/// Return the moves between the first repeated state of `states`, consecutive board states of a game, and its repetition
+///
+/// Each move is given with its resulting state, the last state being the repeated one.
+/// Return `None` if no state is repeated.
+pub fn repetition_cycle(states: &[BoardState]) -> Option<Vec<(Move, BoardState)>> {
+    let mut first_indices = HashMap::new();
+
+    for (index, state) in states.iter().enumerate() {
+        if let Some(&first_index) = first_indices.get(&state.get_id()) {
+            let cycle = states[first_index..=index]
+                .windows(2)
+                .map(|pair| {
+                    let next_move = pair[0]
+                        .moved_piece_to(&pair[1])
+                        .expect("States should be consecutive");
+                    (next_move, pair[1].clone())
+                })
+                .collect();
+            return Some(cycle);
+        }
+        first_indices.insert(state.get_id(), index);
+    }
+
+    None
+}
+
+/// Return the moves of `cycle` (see `repetition_cycle`) as a single line, such as `top 3, left 0, top 3, left 0`
+pub fn describe_cycle(cycle: &[(Move, BoardState)]) -> String {
+    cycle
+        .iter()
+        .map(|(next_move, next_state)| {
+            // The moved piece belongs to the player who isn't next.
+            format!(
+                "{} {}",
+                ["top", "left"][1 - next_state.get_next_player()],
+                next_move
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Let the computer play one game against itself after each possible move from `init_state`
+///
+/// Both players always choose the lowest piece among their best moves, so each game is played once.
+/// Return the moved piece of each opening with the winner of its game, or `None` if a state is repeated (the game is a draw).
+pub fn opening_outcomes(
+    database: &StateDatabase,
+    init_state: &BoardState,
+) -> Vec<(usize, Option<usize>)> {
+    abort_if_id_is_invalid(database, init_state.get_id(), init_state.get_ruleset());
+
+    let mut outcomes = Vec::new();
+
+    for piece in init_state.movable_labels() {
+        let mut state = init_state
+            .get_next_state(piece)
+            .expect("Movable pieces have a next state");
+        let mut seen_ids = HashSet::from([state.get_id()]);
+        let mut winner_opt = None;
+
+        loop {
+            if state.is_ended() {
+                winner_opt = Some(1 - state.get_next_player());
+                break;
+            }
+
+            let next_states: Vec<BoardState> = state.get_next_states().collect();
+            state = select_best_next_state(database, state.get_next_player(), &next_states)
+                .0
+                .expect("There should be a next state");
+
+            // Since moves are chosen deterministically, a repeated state would repeat forever.
+            if !seen_ids.insert(state.get_id()) {
+                break;
+            }
+        }
+
+        outcomes.push((piece, winner_opt));
+    }
+
+    outcomes
+}
+
+/// Let the computer play `games` games against itself from the board state `init_id` and count collisions
+///
+/// Games are stopped after `max_moves` moves, since drawn games never end.
+/// Return the number of moves which sent a piece back and the total number of moves.
+pub fn collision_stats(
+    database: &StateDatabase,
+    init_id: u64,
+    ruleset: &'static Ruleset,
+    games: usize,
+    max_moves: usize,
+) -> (usize, usize) {
+    abort_if_id_is_invalid(database, init_id, ruleset);
+
+    let mut collisions = 0;
+    let mut moves = 0;
+
+    for _game in 0..games {
+        let mut state = BoardState::from_id_with_ruleset(init_id, ruleset);
+
+        for _move in 0..max_moves {
+            if state.is_ended() {
+                break;
+            }
+
+            let next_state = get_best_next_state(database, state.clone())
+                .0
+                .expect("There should be a next state");
+
+            let collision = state.movable_labels().into_iter().any(|piece| {
+                state
+                    .get_next_state_and_collision(piece)
+                    .is_some_and(|(s, collision)| collision && s.get_id() == next_state.get_id())
+            });
+
+            if collision {
+                collisions += 1;
+            }
+            moves += 1;
+            state = next_state;
+        }
+    }
+
+    (collisions, moves)
+}
+
+/// Show `states` one after the other on the terminal, each one replacing the previous one after `delay`
+///
+/// With `highlight`, the pieces moved by the last move are highlighted (see `BoardState::render_last_move`).
+pub fn animate_replay(states: &[BoardState], delay: Duration, highlight: bool) {
+    write_replay(&mut io::stdout(), states, delay, highlight).expect("stdout should be writable");
+}
+
+/// Return the board state of ply `ply` of `states`, highlighting the pieces moved since the previous ply if `highlight` is set
+fn render_ply(states: &[BoardState], ply: usize, highlight: bool) -> String {
+    if highlight && ply > 0 {
+        states[ply].render_last_move(&states[ply - 1])
+    } else {
+        states[ply].to_string()
+    }
+}
+
+/// Write `states` to `writer`, moving the cursor back up before each state to overwrite the previous one
+fn write_replay(
+    writer: &mut impl Write,
+    states: &[BoardState],
+    delay: Duration,
+    highlight: bool,
+) -> io::Result<()> {
+    let mut previous_lines = 0;
+
+    for index in 0..states.len() {
+        if index > 0 {
+            thread::sleep(delay);
+
+            // Move the cursor to the beginning of the line, `previous_lines` lines up.
+            write!(writer, "\x1b[{}F", previous_lines)?;
+        }
+
+        let frame = format!(
+            "{}\nMove {} of {}",
+            render_ply(states, index, highlight),
+            index,
+            states.len() - 1
+        );
+        previous_lines = frame.lines().count() - 1;
+
+        // Clear the end of each line, in case the previous frame was longer.
+        for (line_index, line) in frame.lines().enumerate() {
+            if line_index > 0 {
+                writeln!(writer)?;
+            }
+            write!(writer, "{}\x1b[K", line)?;
+        }
+        writer.flush()?;
+    }
+
+    writeln!(writer)
+}
+
+/// Let the user browse `states` on the terminal, moving between plies with commands read from stdin
+///
+/// With `highlight`, the pieces moved by the last move are highlighted (see `BoardState::render_last_move`).
+pub fn browse_replay(states: &[BoardState], highlight: bool) {
+    write_browsed_replay(&mut io::stdout(), io::stdin().lock(), states, highlight)
+        .expect("stdout should be writable");
+}
+
+/// Write the state of `states` chosen by each command read from `reader`, until the user quits or input ends
+///
+/// An empty line or `n` shows the next ply, `p` the previous one, `s` and `e` the start and end of the game,
+/// and a number the ply with that number.
+fn write_browsed_replay(
+    writer: &mut impl Write,
+    mut reader: impl BufRead,
+    states: &[BoardState],
+    highlight: bool,
+) -> io::Result<()> {
+    let last_ply = states.len() - 1;
+    let mut ply = 0;
+
+    loop {
+        writeln!(
+            writer,
+            "\n{}\nMove {} of {}",
+            render_ply(states, ply, highlight),
+            ply,
+            last_ply
+        )?;
+        write!(
+            writer,
+            "\n[n]ext, [p]revious, [s]tart, [e]nd, move number or [q]uit : "
+        )?;
+        writer.flush()?;
+
+        let mut input = String::new();
+        if reader.read_line(&mut input)? == 0 {
+            return writeln!(writer); // End of user input.
+        }
+
+        ply = match input.trim() {
+            "" | "n" => (ply + 1).min(last_ply),
+            "p" => ply.saturating_sub(1),
+            "s" => 0,
+            "e" => last_ply,
+            "q" => return Ok(()),
+            number => match number.parse::<usize>() {
+                Ok(number) if number <= last_ply => number,
+                _ => {
+                    writeln!(
+                        writer,
+                        "Invalid command! Moves are numbered from 0 to {}",
+                        last_ply
+                    )?;
+                    ply
+                }
+            },
+        };
+    }
+}
+
+/// Terminate thread if `id` does not represent a valid board state following `ruleset`, present in `database`
+///
+/// Board states which can't occur in any game are distinguished from those absent from the data files.
+fn abort_if_id_is_invalid(database: &StateDatabase, id: u64, ruleset: &'static Ruleset) {
+    if !BoardState::from_id_with_ruleset(id, ruleset).is_well_formed() {
+        panic!("Malformed board state ID : {}", id);
+    }
+
+    if !database.contains(id).unwrap_or_else(|e| panic!("{}", e)) {
+        panic!("Board state ID not found in data files : {}", id);
     }
 }
 
@@ -172,14 +793,24 @@ fn abort_if_id_is_invalid(id: u64) {
 mod tests {
     use std::slice;
 
-    use crate::generate::generate;
+    use crate::file_operations::{self, DataFormat};
+    use crate::generate::tests::generate_in;
+    use crate::generate::{collect_reachable_states, generate, Verbosity};
 
     use super::*;
 
     #[test]
     fn validate_id_and_play() {
-        let get_play_result =
-            |id, human_player_opt| std::panic::catch_unwind(|| play(id, human_player_opt, false));
+        let get_play_result = |id, human_player_opt| {
+            std::panic::catch_unwind(|| {
+                play(
+                    id,
+                    &Ruleset::OFFICIAL,
+                    human_player_opt,
+                    &PlayOptions::default(),
+                )
+            })
+        };
 
         let init_state = BoardState::from(100382226046);
 
@@ -191,7 +822,11 @@ mod tests {
                 assert!(get_play_result(id, None).is_err());
             }
 
-            generate(slice::from_ref(&init_state));
+            generate(
+                slice::from_ref(&init_state),
+                DataFormat::Zip,
+                Verbosity::Normal,
+            );
 
             for id in err_id {
                 assert!(get_play_result(id, None).is_err());
@@ -208,7 +843,11 @@ mod tests {
         let init_state = BoardState::from(85065666045);
 
         file_operations::tests::run_in_tempdir(|| {
-            generate(slice::from_ref(&init_state));
+            generate(
+                slice::from_ref(&init_state),
+                DataFormat::Zip,
+                Verbosity::Normal,
+            );
 
             for _i in 0..25 {
                 let first_moved_piece = vec![0, 1, 4][fastrand::usize(0..3)];
@@ -216,9 +855,15 @@ mod tests {
                     .get_next_state(first_moved_piece)
                     .expect("Pieces 0, 1 and 4 should be movable");
 
-                let (all_states, winner) = play(second_state.get_id(), None, false);
+                let (all_states, winner, end_reason) = play(
+                    second_state.get_id(),
+                    &Ruleset::OFFICIAL,
+                    None,
+                    &PlayOptions::default(),
+                );
 
                 assert_eq!(winner, if first_moved_piece == 4 { 1 } else { 0 });
+                assert_eq!(end_reason, EndReason::Finished);
                 assert_eq!(winner, all_states.len() % 2);
 
                 assert!(!all_states.is_empty());
@@ -239,6 +884,55 @@ mod tests {
         });
     }
 
+    #[test]
+    fn shared_database() {
+        let init_state = BoardState::from(85065666045);
+
+        file_operations::tests::with_tempdir(|dir| {
+            generate_in(dir, slice::from_ref(&init_state));
+
+            // The data files are not in the current directory.
+            let database = StateDatabase::new(dir);
+            for _i in 0..5 {
+                let (all_states, winner, end_reason) = play_with_database(
+                    &database,
+                    init_state.get_id(),
+                    &Ruleset::OFFICIAL,
+                    None,
+                    &PlayOptions::default(),
+                );
+                assert_eq!(winner, 1);
+                assert_eq!(end_reason, EndReason::Finished);
+                assert!(all_states.last().unwrap().is_ended());
+            }
+        });
+    }
+
+    #[test]
+    fn match_self_play() {
+        // The left player moves and wins from the first state, the top player from the second one.
+        let init_state = BoardState::from(85065666045);
+        let init_states = [init_state.get_next_state(0).unwrap(), init_state.clone()];
+
+        file_operations::tests::with_tempdir(|dir| {
+            generate_in(dir, slice::from_ref(&init_state));
+            let database = StateDatabase::new(dir);
+
+            assert_eq!(
+                play_match(&database, &init_states, 5, 0, None, &PlayOptions::default()),
+                [3, 2]
+            );
+            assert_eq!(
+                play_match(&database, &init_states, 5, 1, None, &PlayOptions::default()),
+                [2, 3]
+            );
+            assert_eq!(
+                play_match(&database, &init_states, 0, 1, None, &PlayOptions::default()),
+                [0, 0]
+            );
+        });
+    }
+
     #[test]
     fn play_and_await_input() {
         use std::sync::mpsc;
@@ -247,16 +941,30 @@ mod tests {
         let init_state = BoardState::from(init_id);
 
         file_operations::tests::run_in_tempdir(|| {
-            generate(slice::from_ref(&init_state));
+            generate(
+                slice::from_ref(&init_state),
+                DataFormat::Zip,
+                Verbosity::Normal,
+            );
 
             for human_player in (0..=1).rev() {
                 let (send, recv) = mpsc::channel();
 
                 let thread_handle = std::thread::spawn(move || {
                     // The following call should never end IFF `human_player` is 0 AND stdin exists.
-                    let (all_states, winner) = play(init_id, Some(human_player), false);
+                    let (all_states, winner, end_reason) = play(
+                        init_id,
+                        &Ruleset::OFFICIAL,
+                        Some(human_player),
+                        &PlayOptions::default(),
+                    );
 
                     assert_eq!(winner, 1 - human_player);
+                    if human_player == 1 {
+                        assert_eq!(end_reason, EndReason::Finished);
+                    } else {
+                        assert_eq!(end_reason, EndReason::InputClosed);
+                    }
                     assert_eq!(all_states.len(), 1 + human_player);
 
                     let last_state = all_states.last().unwrap();
@@ -294,14 +1002,17 @@ mod tests {
 
                 let next_index = current_index_opt.unwrap() + 1;
                 if next_index == random_next_states.len() {
-                    (None, None)
+                    (Err(EndReason::Resigned), None)
                 } else {
-                    (Some(random_next_states[next_index].clone()), None)
+                    (Ok(random_next_states[next_index].clone()), None)
                 }
             };
 
-            let (all_states, winner) =
-                print_all_states(random_next_states[0].clone(), &get_next_state, false);
+            let (all_states, winner, end_reason) = print_all_states(
+                random_next_states[0].clone(),
+                &get_next_state,
+                &PlayOptions::default(),
+            );
 
             assert_eq!(all_states.len(), random_next_states.len());
             for (index, state) in all_states.iter().enumerate() {
@@ -309,6 +1020,7 @@ mod tests {
             }
 
             assert_eq!(1 - winner, all_states.len() % 2);
+            assert_eq!(end_reason, EndReason::Finished);
         }
     }
 
@@ -329,15 +1041,20 @@ mod tests {
 
             let next_index = current_index_opt.unwrap() + 1;
             if next_index == next_states.len() {
-                (None, None)
+                (Err(EndReason::Resigned), None)
             } else {
-                (Some(next_states[next_index].clone()), None)
+                (Ok(next_states[next_index].clone()), None)
             }
         };
 
-        let (all_states, winner) = print_all_states(next_states[0].clone(), &get_next_state, false);
+        let (all_states, winner, end_reason) = print_all_states(
+            next_states[0].clone(),
+            &get_next_state,
+            &PlayOptions::default(),
+        );
 
         assert_eq!(winner, 0);
+        assert_eq!(end_reason, EndReason::Resigned);
         assert_eq!(all_states.len(), next_states.len());
         for (index, state) in all_states.iter().enumerate() {
             assert_eq!(state.get_id(), next_states[index].get_id());
@@ -346,36 +1063,83 @@ mod tests {
 
     #[test]
     fn human_input() {
-        let check_result = |id, input, expected_id_opt: Option<u64>| {
-            let (state_opt, eval_opt) = get_next_state_from_user_input(BoardState::from(id), input);
-            assert_eq!(state_opt.is_none(), expected_id_opt.is_none());
-            assert_eq!(eval_opt, None);
-            if let Some(expected_id) = expected_id_opt {
-                assert_eq!(state_opt.unwrap().get_id(), expected_id);
+        let check_result = |id, input: &[u8], expected: Result<u64, EndReason>| {
+            // Redrawing the board doesn't change the result.
+            for redraw in [false, true] {
+                let (state_result, eval_opt) = get_next_state_from_user_input(
+                    BoardState::from(id),
+                    input,
+                    &PlayOptions {
+                        redraw,
+                        ..PlayOptions::default()
+                    },
+                );
+                assert_eq!(state_result.map(|s| s.get_id()), expected);
+                assert_eq!(eval_opt, None);
             }
         };
 
-        check_result(100382226046, &b"2\n0\n"[..], None);
-        check_result(100382226046, &b"\xDF\n \n"[..], None);
-        check_result(100382226046, &b"\x82\xe6\n\xDF\n1"[..], Some(100442443391));
-        check_result(100382226046, &b"\n\n\n0\n1\n"[..], Some(100442443391));
-        check_result(100382226046, &b"0\r\n1\r\n"[..], Some(100442443391));
-        check_result(100382226046, &b"2\n0\n3\n1\n"[..], Some(100382229503));
-        check_result(100382226046, &b"1 3\n2\n3\n"[..], Some(100382229503));
+        check_result(100382226046, &b"2\n0\n"[..], Err(EndReason::InputClosed));
+        check_result(100382226046, &b"\xDF\n \n"[..], Err(EndReason::InputClosed));
+        check_result(100382226046, &b"\x82\xe6\n\xDF\n1"[..], Ok(100442443391));
+        check_result(100382226046, &b"\n\n\n0\n1\n"[..], Ok(100442443391));
+        check_result(100382226046, &b"0\r\n1\r\n"[..], Ok(100442443391));
+        check_result(100382226046, &b"2\n0\n3\n1\n"[..], Ok(100382229503));
+        check_result(100382226046, &b"1 3\n2\n3\n"[..], Ok(100382229503));
+
+        // Input ending in the middle of an invalid line is closed as well.
+        check_result(100382226046, &b"2\n0"[..], Err(EndReason::InputClosed));
+        check_result(100382226046, &b"2\nabc"[..], Err(EndReason::InputClosed));
+
+        // Quitting is distinct from closing input.
+        check_result(100382226046, &b"q\n1\n"[..], Err(EndReason::Resigned));
+        check_result(100382226046, &b"2\n quit"[..], Err(EndReason::Resigned));
+
+        // Previewing a move doesn't play it.
+        check_result(100382226046, &b"?1\n3\n"[..], Ok(100382229503));
+        check_result(100382226046, &b"? 3\n?2\n1\n"[..], Ok(100442443391));
+        check_result(100382226046, &b"?1"[..], Err(EndReason::InputClosed));
+
+        // The top player can also give the column of a piece, but not its row.
+        check_result(100382226046, &b"column 2\n"[..], Ok(100442443391));
+        check_result(
+            100382226046,
+            &b"row 2\nCol 0\ncol 4\n"[..],
+            Ok(100382229503),
+        );
+        check_result(100382226046, &b"?column 4\n1\n"[..], Ok(100442443391));
+    }
+
+    #[test]
+    fn piece_designation() {
+        assert_eq!(parse_piece("3\n", 0), Move::new(3));
+        assert_eq!(parse_piece(" 2 ", 1), Move::new(2));
+        assert_eq!(parse_piece(" 7 ", 1), None);
+        assert_eq!(parse_piece("column 6", 0), None);
+        assert_eq!(parse_piece("column 1", 0), Move::new(0));
+        assert_eq!(parse_piece("COL 5\n", 0), Move::new(4));
+        assert_eq!(parse_piece("row 3", 1), Move::new(2));
+        assert_eq!(parse_piece("row 3", 0), None);
+        assert_eq!(parse_piece("column 3", 1), None);
+        assert_eq!(parse_piece("row 0", 1), None);
+        assert_eq!(parse_piece("row", 1), None);
+        assert_eq!(parse_piece("1 3", 0), None);
+        assert_eq!(parse_piece("", 0), None);
     }
 
     #[test]
     fn best_outcome() {
         let init_states = [5057791486, 85065666045].map(BoardState::from);
 
-        let check_result = |id, expected_ids: &[u64], expected_eval| {
-            let (state_opt, eval_opt) = get_best_next_state(BoardState::from(id));
-            assert!(expected_ids.contains(&state_opt.unwrap().get_id()));
-            assert_eq!(eval_opt, Some(expected_eval));
-        };
+        file_operations::tests::with_tempdir(|dir| {
+            generate_in(dir, &init_states);
+            let database = StateDatabase::new(dir);
 
-        file_operations::tests::run_in_tempdir(|| {
-            generate(&init_states);
+            let check_result = |id, expected_ids: &[u64], expected_eval| {
+                let (state_opt, eval_opt) = get_best_next_state(&database, BoardState::from(id));
+                assert!(expected_ids.contains(&state_opt.unwrap().get_id()));
+                assert_eq!(eval_opt, Some(expected_eval));
+            };
 
             check_result(85065666045, &[85065666046], BoardStateEval::Win);
 
@@ -388,7 +1152,7 @@ mod tests {
 
                 let mut state = BoardState::from(85065666045);
                 while !state.is_ended() {
-                    let (state_opt, eval_opt) = get_best_next_state(state);
+                    let (state_opt, eval_opt) = get_best_next_state(&database, state);
                     state = state_opt.unwrap();
 
                     if state.get_next_player() == 0 {
@@ -404,7 +1168,7 @@ mod tests {
 
             let mut state = BoardState::from(5057791486);
             for _i in 0..25 {
-                let (state_opt, eval_opt) = get_best_next_state(state);
+                let (state_opt, eval_opt) = get_best_next_state(&database, state);
                 state = state_opt.unwrap();
 
                 assert!(!state.is_ended());
@@ -414,39 +1178,509 @@ mod tests {
     }
 
     #[test]
-    fn validate_id() {
-        let get_abort_result = |id| {
-            std::panic::catch_unwind(|| {
-                abort_if_id_is_invalid(id);
-            })
-        };
+    fn partial_database() {
+        let state = BoardState::from(85065666045);
+        let losing_state = state.get_next_state(0).unwrap();
+        let winning_state = BoardState::from(85065666046);
+
+        file_operations::tests::with_tempdir(|dir| {
+            let losing_dir = dir.join("losing");
+            std::fs::create_dir(&losing_dir).unwrap();
+            generate_in(&losing_dir, slice::from_ref(&losing_state));
+
+            // Only the losing next state is known, the others are better.
+            let (state_result, eval_opt) =
+                get_best_next_state(&StateDatabase::new(&losing_dir), state.clone());
+            let next_state = state_result.unwrap();
+            assert_ne!(next_state.get_id(), losing_state.get_id());
+            assert!(state
+                .get_next_states()
+                .any(|s| s.get_id() == next_state.get_id()));
+            assert_eq!(eval_opt, Some(BoardStateEval::Unknown));
+
+            // A known winning state is better than unknown states.
+            generate_in(dir, slice::from_ref(&winning_state));
+            let (state_result, eval_opt) =
+                get_best_next_state(&StateDatabase::new(dir), state.clone());
+            assert_eq!(state_result.unwrap().get_id(), winning_state.get_id());
+            assert_eq!(eval_opt, Some(BoardStateEval::Win));
+        });
+    }
+
+    #[test]
+    fn draws() {
+        let init_states = [5057791486, 85065666045].map(BoardState::from);
+
+        file_operations::tests::with_tempdir(|dir| {
+            generate_in(dir, &init_states);
+            let database = StateDatabase::new(dir);
+
+            let state = BoardState::from(5057791486);
+            let draws = drawing_moves(&database, &state);
+            assert!(!draws.is_empty());
+
+            for next_move in state.movable_moves() {
+                let next_id = state.get_next_state_for(next_move).unwrap().get_id();
+                assert_eq!(
+                    draws.contains(&next_move),
+                    database.evaluate(next_id).unwrap().is_none()
+                );
+            }
+
+            // The best move from a drawn state is one of the drawing moves.
+            for _i in 0..25 {
+                let (state_opt, _) = get_best_next_state(&database, state.clone());
+                let next_id = state_opt.unwrap().get_id();
+                assert!(draws.iter().any(|&next_move| state
+                    .get_next_state_for(next_move)
+                    .unwrap()
+                    .get_id()
+                    == next_id));
+            }
+
+            // There are no draws from a won state.
+            assert!(drawing_moves(&database, &BoardState::from(85065666045)).is_empty());
+        });
+    }
+
+    #[test]
+    fn forced_moves() {
+        let init_state = BoardState::from(85065666045);
+
+        // The next player can only move their last piece.
+        static ALL_PIECES_HOME: Ruleset = Ruleset::OFFICIAL.with_goal(5);
+        let mut state = BoardState::new_game_with_ruleset(0, &ALL_PIECES_HOME);
+        assert_eq!(forced_move(None, &state), None);
+        while state.movable_labels().len() > 1 {
+            let piece = state.movable_labels()[0];
+            state = state.get_next_state(piece).unwrap();
+            state = state.get_next_state(state.movable_labels()[0]).unwrap();
+        }
+        assert_eq!(forced_move(None, &state), Some(state.movable_moves()[0]));
+
+        file_operations::tests::with_tempdir(|dir| {
+            generate_in(dir, slice::from_ref(&init_state));
+            let database = StateDatabase::new(dir);
+
+            let mut forced_states = 0;
+            for id in collect_reachable_states(slice::from_ref(&init_state)) {
+                let state = BoardState::from(id);
+                if state.is_ended() {
+                    continue;
+                }
+
+                let last_player = 1 - state.get_next_player();
+                let non_losing_moves: Vec<Move> = state
+                    .movable_moves()
+                    .into_iter()
+                    .filter(|&next_move| {
+                        let next_id = state.get_next_state_for(next_move).unwrap().get_id();
+                        !database.is_winning(next_id, last_player).unwrap()
+                    })
+                    .collect();
+
+                let forced_move_opt = forced_move(Some(&database), &state);
+                if non_losing_moves.len() == 1 {
+                    assert_eq!(forced_move_opt, Some(non_losing_moves[0]));
+                    forced_states += 1;
+                } else {
+                    assert_eq!(forced_move_opt, None);
+                }
+            }
+            assert!(forced_states > 0);
+        });
+    }
+
+    #[test]
+    fn zugzwangs() {
+        let init_state = BoardState::from(5057791486);
+
+        file_operations::tests::with_tempdir(|dir| {
+            generate_in(dir, slice::from_ref(&init_state));
+            let database = StateDatabase::new(dir);
+            let all_states = file_operations::read_states(database.all_states_path()).unwrap();
+            let winning_states = [0, 1].map(|player| {
+                file_operations::read_states(database.winning_states_path(player)).unwrap()
+            });
+
+            let mut zugzwangs = 0;
+            for id in &all_states {
+                let state = BoardState::from(id);
+                let player = state.get_next_player();
+                // Loss < Draw < Win, unknown states being skipped.
+                let rank = |id| {
+                    if winning_states[player].contains(id) {
+                        Some(2)
+                    } else if winning_states[1 - player].contains(id) {
+                        Some(0)
+                    } else {
+                        all_states.contains(id).then_some(1)
+                    }
+                };
+
+                let passing_rank = rank(state.passed().get_id());
+                let expected = !state.is_ended()
+                    && passing_rank.is_some()
+                    && state
+                        .get_next_states()
+                        .all(|s| rank(s.get_id()).is_some_and(|r| Some(r) < passing_rank));
+                assert_eq!(is_zugzwang(&database, &state), expected);
+                zugzwangs += expected as u32;
+            }
+            assert!(zugzwangs > 0);
+        });
+    }
+
+    #[test]
+    fn openings() {
+        let init_states = [5057791486, 85065666045].map(BoardState::from);
+
+        file_operations::tests::with_tempdir(|dir| {
+            generate_in(dir, &init_states);
+            let database = StateDatabase::new(dir);
+
+            for init_state in &init_states {
+                let outcomes = opening_outcomes(&database, init_state);
+                assert_eq!(
+                    outcomes
+                        .iter()
+                        .map(|&(piece, _)| piece)
+                        .collect::<Vec<usize>>(),
+                    init_state.movable_labels()
+                );
+
+                // Games are played optimally, and are the same each time.
+                for &(piece, winner_opt) in &outcomes {
+                    let next_id = init_state.get_next_state(piece).unwrap().get_id();
+                    assert_eq!(winner_opt, database.evaluate(next_id).unwrap());
+                }
+                assert_eq!(opening_outcomes(&database, init_state), outcomes);
+            }
+        });
+    }
+
+    #[test]
+    fn best_line() {
+        let init_states = [5057791486, 85065666045].map(BoardState::from);
+
+        file_operations::tests::with_tempdir(|dir| {
+            generate_in(dir, &init_states);
+
+            let database = StateDatabase::new(dir);
+            let depths = analysis::depths_to_win(&database, &Ruleset::OFFICIAL).unwrap();
 
-        let error_contains_id = |id| {
-            let result = get_abort_result(id);
-            assert!(result.is_err());
-            assert!(result
-                .unwrap_err()
-                .downcast::<String>()
+            // From a won state, the line is always the fastest win, which ends with the win of the next player.
+            let init_state = BoardState::from(85065666045);
+            let first_line = principal_variation(&database, init_state.clone());
+            for _i in 0..5 {
+                let mut state = init_state.clone();
+                let line = principal_variation(&database, state.clone());
+                assert!(line == first_line);
+
+                assert_eq!(line[0].1.get_id(), 85065666046);
+                assert_eq!(line.len() as u32, depths[&init_state.get_id()]);
+                let (_, last_state) = line.last().unwrap();
+                assert!(last_state.is_ended());
+                assert_eq!(1 - last_state.get_next_player(), 1);
+
+                for (next_move, next_state) in line {
+                    assert_eq!(
+                        state.get_next_state_for(next_move).unwrap().get_id(),
+                        next_state.get_id()
+                    );
+                    state = next_state;
+                }
+            }
+
+            // The loser of a won state plays the longest defense.
+            let lost_state = init_state.get_next_state(0).unwrap();
+            let line = principal_variation(&database, lost_state.clone());
+            assert_eq!(line.len() as u32, depths[&lost_state.get_id()]);
+            assert_eq!(1 - line.last().unwrap().1.get_next_player(), 0);
+
+            // The line of a draw stops at the first repeated state.
+            let line = principal_variation(&database, BoardState::from(5057791486));
+            let (_, last_state) = line.last().unwrap();
+            assert!(!last_state.is_ended());
+            assert!(
+                last_state.get_id() == 5057791486
+                    || line[..line.len() - 1]
+                        .iter()
+                        .any(|(_, s)| s.get_id() == last_state.get_id())
+            );
+
+            // There are no moves from an ended state.
+            let ended_state = principal_variation(&database, BoardState::from(85065666045))
+                .pop()
                 .unwrap()
-                .contains(&id.to_string()));
+                .1;
+            assert!(principal_variation(&database, ended_state).is_empty());
+        });
+    }
+
+    #[test]
+    fn collisions_in_self_play() {
+        let init_states = [5057791486, 85065666045].map(BoardState::from);
+
+        file_operations::tests::with_tempdir(|dir| {
+            generate_in(dir, &init_states);
+
+            let (collisions, moves) = collision_stats(
+                &StateDatabase::new(dir),
+                85065666045,
+                &Ruleset::OFFICIAL,
+                10,
+                1000,
+            );
+            assert!(collisions <= moves);
+            assert!(moves >= 10);
+
+            // Drawn games are stopped after `max_moves` moves.
+            let (collisions, moves) = collision_stats(
+                &StateDatabase::new(dir),
+                5057791486,
+                &Ruleset::OFFICIAL,
+                3,
+                50,
+            );
+            assert!(collisions <= moves);
+            assert_eq!(moves, 150);
+
+            assert_eq!(
+                collision_stats(
+                    &StateDatabase::new(dir),
+                    85065666045,
+                    &Ruleset::OFFICIAL,
+                    0,
+                    1000
+                ),
+                (0, 0)
+            );
+        });
+    }
+
+    #[test]
+    fn step_gate() {
+        assert!(wait_for_step("\n".as_bytes()));
+        assert!(wait_for_step("anything\n".as_bytes()));
+        assert!(wait_for_step("no line break".as_bytes()));
+        assert!(!wait_for_step("".as_bytes()));
+    }
+
+    #[test]
+    fn verdicts_of_moves() {
+        let init_state = BoardState::from(5057791486);
+
+        file_operations::tests::with_tempdir(|dir| {
+            generate_in(dir, slice::from_ref(&init_state));
+            let database = StateDatabase::new(dir);
+
+            for state in collect_reachable_states(slice::from_ref(&init_state))
+                .iter()
+                .take(1000)
+                .map(BoardState::from)
+            {
+                let player = state.get_next_player();
+                let verdicts = move_verdicts(&database, &state);
+                assert_eq!(
+                    verdicts
+                        .iter()
+                        .map(|&(piece, _)| piece)
+                        .collect::<Vec<usize>>(),
+                    state.movable_labels()
+                );
+
+                for &(piece, verdict) in &verdicts {
+                    let next_id = state.get_next_state(piece).unwrap().get_id();
+                    assert_eq!(verdict, database.verdict(next_id, player).unwrap());
+                }
+
+                // The best move gives the outcome of the state itself.
+                let best_verdict = verdicts
+                    .iter()
+                    .map(|&(_, verdict)| verdict)
+                    .max_by(|a, b| a.partial_cmp(b).unwrap());
+                match best_verdict {
+                    Some(verdict) => {
+                        assert_eq!(verdict, database.verdict(state.get_id(), player).unwrap());
+                        assert_eq!(get_best_next_state(&database, state).1, Some(verdict));
+                    }
+                    None => assert!(state.is_ended()),
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn move_timings() {
+        assert_eq!(duration_stats(&[]), None);
+
+        let millis = Duration::from_millis;
+        assert_eq!(
+            duration_stats(&[millis(1)]),
+            Some((millis(1), millis(1), millis(1)))
+        );
+        assert_eq!(
+            duration_stats(&[millis(4), millis(1), millis(7)]),
+            Some((millis(1), millis(4), millis(7)))
+        );
+    }
+
+    #[test]
+    fn repetition_cycles() {
+        let mut states = vec![BoardState::new_game(0)];
+        for piece in [4, 0, 2] {
+            states.push(states.last().unwrap().get_next_state(piece).unwrap());
+        }
+        assert!(repetition_cycle(&states).is_none());
+        assert!(repetition_cycle(&[]).is_none());
+
+        // Both players send each other's pieces back until this state is reached again.
+        let cycle_moves = [3, 3, 3, 0, 3, 0, 3, 3, 0, 0, 0, 0, 0, 3];
+        let mut states = vec![BoardState::from(41188640254)];
+        for piece in cycle_moves.iter().chain(&cycle_moves[..2]) {
+            states.push(states.last().unwrap().get_next_state(*piece).unwrap());
+        }
+
+        let cycle = repetition_cycle(&states).unwrap();
+        assert_eq!(
+            cycle
+                .iter()
+                .map(|(next_move, _)| next_move.piece())
+                .collect::<Vec<usize>>(),
+            cycle_moves
+        );
+        assert_eq!(cycle.last().unwrap().1.get_id(), 41188640254);
+        assert!(describe_cycle(&cycle).starts_with(&format!(
+            "{} 3, {} 3, ",
+            ["top", "left"][states[0].get_next_player()],
+            ["top", "left"][1 - states[0].get_next_player()]
+        )));
+        assert_eq!(describe_cycle(&cycle).matches(", ").count(), 13);
+
+        // The cycle starts at the first occurrence of the repeated state.
+        let cycle = repetition_cycle(&states[1..]).unwrap();
+        assert_eq!(cycle.len(), cycle_moves.len());
+        assert_eq!(cycle[0].0.piece(), cycle_moves[1]);
+    }
+
+    #[test]
+    fn replay_animation() {
+        let mut states = vec![BoardState::new_game(1)];
+        for piece in [0, 4, 2] {
+            states.push(states.last().unwrap().get_next_state(piece).unwrap());
+        }
+
+        let mut output = Vec::new();
+        write_replay(&mut output, &states, Duration::ZERO, false).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        // Each frame is made of the board, its ID and the move number.
+        assert_eq!(output.matches("\x1b[17F").count(), 3);
+        assert_eq!(output.lines().count(), 18 * 4 - 3);
+        for (index, state) in states.iter().enumerate() {
+            assert!(output.contains(&format!("(ID : {})", state.get_id())));
+            assert!(output.contains(&format!("Move {} of 3", index)));
+        }
+        assert!(output.ends_with("Move 3 of 3\x1b[K\n"));
+
+        let mut output = Vec::new();
+        write_replay(&mut output, &states[..1], Duration::ZERO, false).unwrap();
+        assert!(!String::from_utf8(output).unwrap().contains("\x1b[17F"));
+
+        // Highlighted frames show the last moved piece with a double arrow.
+        let mut output = Vec::new();
+        write_replay(&mut output, &states, Duration::ZERO, true).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("\x1b[17F").count(), 3);
+        assert_eq!(output.matches(['⇓', '⇑', '⇒', '⇐']).count(), 3);
+    }
+
+    #[test]
+    fn replay_browsing() {
+        let mut states = vec![BoardState::new_game(1)];
+        for piece in [2, 0, 4] {
+            states.push(states.last().unwrap().get_next_state(piece).unwrap());
+        }
+
+        let get_shown_plies = |input: &str| {
+            let mut output = Vec::new();
+            write_browsed_replay(&mut output, input.as_bytes(), &states, false).unwrap();
+            let output = String::from_utf8(output).unwrap();
+
+            output
+                .lines()
+                .filter_map(|line| line.strip_prefix("Move "))
+                .map(|line| line.split(' ').next().unwrap().parse().unwrap())
+                .collect::<Vec<usize>>()
         };
 
+        assert_eq!(get_shown_plies(""), [0]);
+        assert_eq!(get_shown_plies("\nn\np\n"), [0, 1, 2, 1]);
+        assert_eq!(get_shown_plies("e\nn\ns\np\n"), [0, 3, 3, 0, 0]);
+        assert_eq!(get_shown_plies("2\n4\nx\nq\n1\n"), [0, 2, 2, 2]);
+
+        // Each ply is shown with its board state.
+        let mut output = Vec::new();
+        write_browsed_replay(&mut output, "e\n".as_bytes(), &states, false).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains(&format!("(ID : {})", states[0].get_id())));
+        assert!(output.contains(&format!("(ID : {})", states[3].get_id())));
+        assert!(!output.contains(&format!("(ID : {})", states[1].get_id())));
+        assert!(output.contains("Move 3 of 3"));
+        assert!(!output.contains(['⇓', '⇑', '⇒', '⇐']));
+
+        let mut output = Vec::new();
+        write_browsed_replay(&mut output, "e\n".as_bytes(), &states, true).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains(&states[3].render_last_move(&states[2])));
+    }
+
+    #[test]
+    fn validate_id() {
         let init_state = BoardState::from(85065666045);
 
         let err_id = [0, 1, 85065666044, u64::MAX];
         let ok_id = [init_state.get_id(), 85789186557, 59071845884, 67743143411];
 
-        file_operations::tests::run_in_tempdir(|| {
+        file_operations::tests::with_tempdir(|dir| {
+            let database = StateDatabase::new(dir);
+
+            let get_abort_result = |id| {
+                std::panic::catch_unwind(|| {
+                    abort_if_id_is_invalid(&database, id, &Ruleset::OFFICIAL);
+                })
+            };
+
+            let error_contains_id = |id| {
+                let result = get_abort_result(id);
+                assert!(result.is_err());
+                assert!(result
+                    .unwrap_err()
+                    .downcast::<String>()
+                    .unwrap()
+                    .contains(&id.to_string()));
+            };
+
             for &id in err_id.iter().chain(ok_id.iter()) {
                 assert!(get_abort_result(id).is_err());
             }
 
-            generate(slice::from_ref(&init_state));
+            generate_in(dir, slice::from_ref(&init_state));
 
             for id in err_id {
                 error_contains_id(id);
             }
 
+            let get_error_message = |id| {
+                *get_abort_result(id)
+                    .unwrap_err()
+                    .downcast::<String>()
+                    .unwrap()
+            };
+            assert!(get_error_message(u64::MAX).starts_with("Malformed"));
+            assert!(get_error_message(0).contains("not found"));
+
             for id in ok_id {
                 assert!(get_abort_result(id).is_ok());
             }
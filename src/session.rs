@@ -0,0 +1,302 @@
+use std::fs;
+use std::io;
+
+use crate::board_state::{BoardState, Move, Ruleset};
+
+/// Game interrupted by a human player, which can be saved and resumed later
+#[derive(Debug, PartialEq)]
+pub struct Session {
+    /// ID of the board state the game started from
+    pub init_id: u64,
+
+    /// Moves played since the start of the game
+    pub moves: Vec<Move>,
+}
+
+impl Session {
+    /// Create a session for a game starting from the board state `init_id`
+    pub fn new(init_id: u64) -> Self {
+        Session {
+            init_id,
+            moves: Vec::new(),
+        }
+    }
+
+    /// Append the moves leading from the first to the last of `states`, which must be consecutive board states
+    pub fn extend_from_states(&mut self, states: &[BoardState]) {
+        for pair in states.windows(2) {
+            let next_move = pair[0]
+                .moved_piece_to(&pair[1])
+                .expect("States should be consecutive");
+
+            self.moves.push(next_move);
+        }
+    }
+
+    /// Replay the session following `ruleset` and return all states of the game
+    ///
+    /// Return `None` if a move of the session is invalid, i.e. if it moves a piece which has reached its final position
+    /// or follows the end of the game.
+    pub fn replay(&self, ruleset: &'static Ruleset) -> Option<Vec<BoardState>> {
+        let mut states = vec![BoardState::from_id_with_ruleset(self.init_id, ruleset)];
+
+        for &next_move in &self.moves {
+            let state = states.last().expect("There should be at least one state");
+            if state.is_ended() {
+                return None;
+            }
+
+            let next_state = state.get_next_state_for(next_move)?;
+            states.push(next_state);
+        }
+
+        Some(states)
+    }
+
+    /// Return the number of plies played since each piece last moved, given [player][piece]
+    ///
+    /// Pieces which haven't moved during the session count all its plies.
+    pub fn plies_since_moved(&self) -> [[u32; 5]; 2] {
+        // The next player is stored in the same way in all rulesets.
+        let first_player = BoardState::from(self.init_id).get_next_player();
+        let mut plies = [[self.moves.len() as u32; 5]; 2];
+
+        for (index, &next_move) in self.moves.iter().enumerate() {
+            let player = (first_player + index) % 2;
+            plies[player][next_move.piece()] = (self.moves.len() - index - 1) as u32;
+        }
+
+        plies
+    }
+
+    /// Save the session to file `path`, overwriting it if it exists
+    ///
+    /// The file contains the initial board state ID on the first line and the moved pieces on the second one.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let moves = self
+            .moves
+            .iter()
+            .map(|next_move| next_move.to_string())
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        fs::write(path, format!("{}\n{}\n", self.init_id, moves))
+    }
+
+    /// Load a session previously saved to file `path`
+    ///
+    /// Return an `InvalidData` error if the file isn't a session file, or if a moved piece is not a piece number.
+    pub fn load(path: &str) -> io::Result<Self> {
+        let invalid_data = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid session file : {}", path),
+            )
+        };
+
+        let contents = fs::read_to_string(path)?;
+        let mut lines = contents.lines();
+
+        let init_id = lines
+            .next()
+            .and_then(|line| line.trim().parse().ok())
+            .ok_or_else(invalid_data)?;
+
+        let moves = lines
+            .next()
+            .unwrap_or("")
+            .split_whitespace()
+            .map(|piece| {
+                piece
+                    .parse()
+                    .ok()
+                    .and_then(Move::new)
+                    .ok_or_else(invalid_data)
+            })
+            .collect::<io::Result<Vec<Move>>>()?;
+
+        Ok(Session { init_id, moves })
+    }
+}
+
+/// Return an identifier of the game starting from `init_state` in which `moves` are played, or `None` if a move is invalid
+///
+/// The identifier is a 64-bit FNV-1a hash of the initial board state ID and the moved pieces, which doesn't depend on the
+/// platform or the Rust version, so it can be stored to find duplicate games.
+pub fn game_hash(init_state: &BoardState, moves: &[Move]) -> Option<u64> {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+
+    let mut state = init_state.clone();
+    for &next_move in moves {
+        if state.is_ended() {
+            return None;
+        }
+        state = state.get_next_state_for(next_move)?;
+    }
+
+    // Moved pieces are single bytes, after the 8 bytes of the ID.
+    let bytes = init_state
+        .get_id()
+        .to_le_bytes()
+        .into_iter()
+        .chain(moves.iter().map(|next_move| next_move.piece() as u8));
+
+    Some(bytes.fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::file_operations;
+
+    use super::*;
+
+    fn moves(pieces: &[usize]) -> Vec<Move> {
+        pieces
+            .iter()
+            .map(|&piece| Move::new(piece).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn replay() {
+        let init_state = BoardState::new_game(0);
+        let mut states = vec![init_state.clone()];
+        for piece in [4, 0, 2] {
+            states.push(states.last().unwrap().get_next_state(piece).unwrap());
+        }
+
+        let mut session = Session::new(init_state.get_id());
+        session.extend_from_states(&states[..1]);
+        assert!(session.moves.is_empty());
+
+        session.extend_from_states(&states);
+        assert_eq!(session.moves, moves(&[4, 0, 2]));
+
+        let replayed_ids: Vec<u64> = session
+            .replay(&Ruleset::OFFICIAL)
+            .unwrap()
+            .iter()
+            .map(|s| s.get_id())
+            .collect();
+        assert_eq!(
+            replayed_ids,
+            states.iter().map(|s| s.get_id()).collect::<Vec<u64>>()
+        );
+
+        // Moves of pieces which have reached their final position are rejected.
+        let session = Session {
+            init_id: BoardState::from_positions_unchecked(0, [[12, 0, 0, 0, 0], [0; 5]]).get_id(),
+            moves: moves(&[0]),
+        };
+        assert!(session.replay(&Ruleset::OFFICIAL).is_none());
+
+        // No move can follow the end of the game.
+        let session = Session {
+            init_id: BoardState::from_positions_unchecked(0, [[0; 5], [12, 12, 12, 12, 0]])
+                .get_id(),
+            moves: moves(&[0]),
+        };
+        assert!(session.replay(&Ruleset::OFFICIAL).is_none());
+    }
+
+    #[test]
+    fn game_hashes() {
+        let init_state = BoardState::new_game(0);
+        let hash = game_hash(&init_state, &moves(&[4, 0, 2])).unwrap();
+
+        assert_eq!(
+            game_hash(&init_state.clone(), &moves(&[4, 0, 2])),
+            Some(hash)
+        );
+        assert_ne!(game_hash(&init_state, &moves(&[4, 0, 1])), Some(hash));
+        assert_ne!(game_hash(&init_state, &moves(&[4, 0])), Some(hash));
+        assert_ne!(
+            game_hash(&BoardState::new_game(1), &moves(&[4, 0, 2])),
+            Some(hash)
+        );
+
+        // The hash is the same on all platforms.
+        assert_eq!(game_hash(&init_state, &[]), Some(0xa8c7f832281a39c5));
+
+        // No move can follow the end of the game.
+        let mut game_moves = Vec::new();
+        let mut state = init_state.clone();
+        while !state.is_ended() {
+            game_moves.push(state.movable_moves()[0]);
+            state = state
+                .get_next_state_for(game_moves[game_moves.len() - 1])
+                .unwrap();
+        }
+        assert!(game_hash(&init_state, &game_moves).is_some());
+        game_moves.push(game_moves[0]);
+        assert_eq!(game_hash(&init_state, &game_moves), None);
+
+        let mut hashes = std::collections::HashSet::new();
+        for first_piece in 0..5 {
+            for second_piece in 0..5 {
+                hashes
+                    .insert(game_hash(&init_state, &moves(&[first_piece, second_piece])).unwrap());
+            }
+        }
+        assert_eq!(hashes.len(), 25);
+    }
+
+    #[test]
+    fn staleness() {
+        let mut session = Session::new(BoardState::new_game(0).get_id());
+        assert_eq!(session.plies_since_moved(), [[0; 5]; 2]);
+
+        // Top moves 4, left moves 0, top moves 2, left moves 0 again.
+        session.moves = moves(&[4, 0, 2, 0]);
+        assert_eq!(
+            session.plies_since_moved(),
+            [[4, 4, 1, 4, 3], [0, 4, 4, 4, 4]]
+        );
+
+        let session = Session {
+            init_id: BoardState::new_game(1).get_id(),
+            moves: moves(&[3]),
+        };
+        assert_eq!(
+            session.plies_since_moved(),
+            [[1, 1, 1, 1, 1], [1, 1, 1, 0, 1]]
+        );
+    }
+
+    #[test]
+    fn save_and_load() {
+        file_operations::tests::with_tempdir(|dir| {
+            let path = file_operations::data_file_path(dir, "", "session.txt");
+
+            assert!(Session::load(&path).is_err());
+
+            for session in [
+                Session::new(85065666045),
+                Session {
+                    init_id: 85065666045,
+                    moves: moves(&[4, 0, 2]),
+                },
+            ] {
+                session.save(&path).unwrap();
+                assert_eq!(Session::load(&path).unwrap(), session);
+            }
+
+            for contents in [
+                "",
+                "abc\n",
+                "85065666045\n4 x\n",
+                "85065666045\n-1\n",
+                "85065666045\n4 5\n",
+            ] {
+                fs::write(&path, contents).unwrap();
+                assert_eq!(
+                    Session::load(&path).unwrap_err().kind(),
+                    io::ErrorKind::InvalidData
+                );
+            }
+        });
+    }
+}